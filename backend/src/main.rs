@@ -1,21 +1,150 @@
-use actix_web::{web, App, HttpServer, HttpResponse, Result};
+use actix_web::{guard, http::header, web, App, HttpRequest, HttpServer, HttpResponse, Result};
 use actix_cors::Cors;
+use actix_multipart::Multipart;
+use async_trait::async_trait;
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use futures_util::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::{sqlite::{SqliteConnectOptions, SqlitePoolOptions}, SqlitePool};
+use sqlx::{sqlite::{SqliteConnectOptions, SqlitePoolOptions}, QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
-const XAI_API_URL: &str = "https://api.x.ai/v1/chat/completions";
-const XAI_MODEL: &str = "grok-3-mini";
-const GEMINI_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta/openai/chat/completions";
+const XAI_BASE_URL: &str = "https://api.x.ai/v1";
+const XAI_MODEL_DEFAULT: &str = "grok-3-mini";
+const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta/openai";
 const GEMINI_MODEL_DEFAULT: &str = "gemini-2.5-flash";
 
 const VALID_STATUSES: [&str; 4] = ["todo", "in_progress", "done", "blocked"];
+const RULE_KINDS: [&str; 3] = ["require_notes", "require_assignee", "require_comment"];
+const WIP_SCOPES: [&str; 2] = ["project", "assignee"];
+const WIP_MODES: [&str; 2] = ["reject", "warn"];
+const RANK_DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const DUPLICATE_THRESHOLD: f64 = 0.6;
+const EMBEDDING_DUPLICATE_THRESHOLD: f64 = 0.85;
+/// Open tasks loaded as duplicate candidates per task checked, and in total.
+const DUPLICATE_CANDIDATES_PER_TASK: usize = 100;
+const DUPLICATE_CANDIDATES_MAX: usize = 1000;
+/// Past tasks less similar than this are ignored when suggesting an assignee and tags.
+const SUGGESTION_MIN_SIMILARITY: f64 = 0.2;
+/// Past tasks loaded to suggest from, newest first.
+const SUGGESTION_HISTORY_MAX: i64 = 2000;
+/// Added to the total weight when computing a suggestion's confidence, so a single weak
+/// match does not produce a confident suggestion.
+const SUGGESTION_PRIOR: f64 = 0.5;
+const SIMILARITY_STOPWORDS: [&str; 24] = [
+    "a", "an", "and", "as", "at", "be", "by", "for", "from", "in", "is", "it", "of", "on", "or", "our", "the", "this",
+    "that", "to", "up", "we", "will", "with",
+];
 
-#[derive(Clone, Copy, PartialEq)]
-enum AiProvider {
-    Xai,
-    Gemini,
-}
+const EXTRACTION_PROMPT: &str = "task_extraction";
+const SUMMARY_PROMPT: &str = "sprint_summary";
+const QUERY_PROMPT: &str = "task_query";
+const SUGGESTION_PROMPT: &str = "task_suggestion";
+const BREAKDOWN_PROMPT: &str = "task_breakdown";
+
+/// Built-in prompt templates, stored as version 1 of each name on first start.
+const PROMPT_TEMPLATES: &[(&str, &str)] = &[
+    (
+        EXTRACTION_PROMPT,
+        r#"You are a task extraction assistant. Given meeting notes or any text, extract actionable tasks.
+
+Return ONLY a valid JSON object of the form {"tasks": [...]}. Each task must have:
+- "title": string (required, concise task title)
+- "description": string (required, detailed description)
+- "tags": array of strings (e.g. ["meeting", "urgent"])
+- "deadline": string or null (YYYY-MM-DD for an exact date; a relative deadline such as "by Friday" or "end of next sprint" copied as written; null if none)
+- "project": string (one of the existing projects below; "General" if none fits)
+- "assignee": string (one of the existing assignees below; "Unassigned" if not specified)
+- "status": string (one of "todo", "in_progress", "done", "blocked"; default "todo")
+
+Today's date is {{today}}. The meeting took place on {{meeting_date}}.
+Existing projects, with their keys: {{projects}}
+Existing assignees, with the other names they go by: {{assignees}}
+Use the full names exactly as listed above, even when the notes use a key, nickname or first name.
+
+{{conventions}}
+
+Example output:
+{"tasks":[{"title":"Review PR #123","description":"Code review for authentication module","tags":["review","urgent"],"deadline":"2025-02-25","project":"Backend","assignee":"Unassigned","status":"todo"}]}"#,
+    ),
+    (
+        SUMMARY_PROMPT,
+        r#"You write concise progress reports for a software team's weekly update email.
+
+Using only the tasks and status changes provided, write a Markdown report with these sections:
+## Summary – two or three sentences on overall progress.
+## Done – tasks completed, grouped by project.
+## In progress – what is being worked on and by whom.
+## Blocked – each blocked task and why, using status change comments and notes.
+## Risks – upcoming or overdue deadlines and anything likely to slip.
+
+Refer to tasks by their key when they have one. Leave out empty sections. Do not invent tasks or details.
+Reply with the Markdown report only.
+
+Today's date is {{today}}.
+
+{{conventions}}"#,
+    ),
+    (
+        QUERY_PROMPT,
+        r#"You translate questions about a team's tasks into a filter for the task list.
+
+Reply with only a JSON object with these fields, using null for anything the question does not ask about:
+- "project": project name; comma-separated for several
+- "assignee": assignee name; comma-separated for several
+- "status": one or more of todo, in_progress, done, blocked, comma-separated
+- "tag": tag; comma-separated for any of several
+- "in_sprint": true or false
+- "archived": true only when the question asks about archived tasks, otherwise false
+- "search": a word or phrase from the task title or description
+- "deadline_before": YYYY-MM-DD, inclusive
+- "deadline_after": YYYY-MM-DD, inclusive
+
+"Overdue" means a deadline before today and a status other than done. "Open" means todo, in_progress or blocked.
+Only use these project names: {{projects}}
+Only use these assignee names: {{assignees}}
+
+Today's date is {{today}}.
+
+{{conventions}}"#,
+    ),
+    (
+        SUGGESTION_PROMPT,
+        r#"You suggest who should work on a task and which tags it should have, based on similar tasks the team worked on before.
+
+Reply with only a JSON object:
+{"assignee": string or null, "tags": [string], "confidence": number from 0 to 1, "reason": one short sentence}
+
+Only suggest one of these assignees: {{assignees}}
+Prefer people who completed similar tasks in the same project, and tags already used on similar tasks.
+Use null for the assignee when the history gives no good reason to pick someone.
+
+{{conventions}}"#,
+    ),
+    (
+        BREAKDOWN_PROMPT,
+        r#"You break a task down into concrete subtasks that together complete it.
+
+Return ONLY a valid JSON object of the form {"subtasks": [...]}. Each subtask must have:
+- "title": string (concise, starting with a verb)
+- "description": string (what to do and how to tell it is done)
+- "tags": array of strings (may be empty)
+- "estimate_hours": number (a realistic estimate of the effort)
+
+Propose between 2 and 8 subtasks, each small enough for one person to finish in two days or less.
+Do not repeat the existing subtasks listed with the task.
+
+Today's date is {{today}}.
+
+{{conventions}}"#,
+    ),
+];
+
+/// Variables that can appear as `{{name}}` in a prompt template.
+const PROMPT_VARIABLES: &[&str] = &["today", "meeting_date", "projects", "assignees", "conventions"];
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct Task {
@@ -35,6 +164,22 @@ struct Task {
     notes: Option<String>,
     #[serde(default)]
     archived: bool,
+    #[serde(default)]
+    rank: String,
+    #[serde(default)]
+    key: Option<String>,
+    /// The meeting whose notes the task was extracted from.
+    #[serde(default)]
+    meeting_id: Option<i64>,
+    /// Version of the extraction prompt template the task was generated with.
+    #[serde(default)]
+    prompt_version: Option<i64>,
+    /// The task this one was broken down from.
+    #[serde(default)]
+    parent_id: Option<i64>,
+    /// Estimated effort in hours.
+    #[serde(default)]
+    estimate_hours: Option<f64>,
 }
 
 fn default_status() -> String {
@@ -43,6 +188,113 @@ fn default_status() -> String {
 
 struct AppState {
     pool: SqlitePool,
+    ai: Arc<dyn AiProvider>,
+    /// Shared by every provider, including per-job overrides, so connections are reused.
+    http: AiHttp,
+    extraction: ExtractionConfig,
+    deadlines: DeadlineConfig,
+    budget: AiBudget,
+    /// Wakes an idle job worker when a job is queued.
+    job_notify: tokio::sync::Notify,
+    /// `JOB_MAX_ATTEMPTS` (default 3): attempts before a retryable failure is final.
+    job_max_attempts: i64,
+}
+
+fn rank_digit(c: u8) -> usize {
+    RANK_DIGITS.iter().position(|&d| d == c).unwrap_or(0)
+}
+
+/// Returns a rank that sorts after `lo` by adding one to its last digit, with carry. Only a
+/// rank of all highest digits grows, and then by as many digits as it has, so appending keeps
+/// ranks short. The result never ends in '0'.
+fn rank_after(lo: &str) -> String {
+    if lo.is_empty() {
+        return (RANK_DIGITS[RANK_DIGITS.len() / 2] as char).to_string();
+    }
+    let mut digits: Vec<usize> = lo.bytes().map(rank_digit).collect();
+    let mut i = digits.len();
+    while i > 0 && digits[i - 1] + 1 == RANK_DIGITS.len() {
+        digits[i - 1] = 0;
+        i -= 1;
+    }
+    let mut out: Vec<u8> = if i == 0 {
+        let mut grown = lo.as_bytes().to_vec();
+        grown.resize(lo.len() * 2, RANK_DIGITS[0]);
+        grown
+    } else {
+        digits[i - 1] += 1;
+        digits.iter().map(|&d| RANK_DIGITS[d]).collect()
+    };
+    if out.last() == Some(&RANK_DIGITS[0]) {
+        *out.last_mut().unwrap() = RANK_DIGITS[1];
+    }
+    String::from_utf8(out).unwrap_or_default()
+}
+
+/// Returns a rank strictly between `lo` and `hi` (`hi = None` means unbounded).
+/// Ranks never end in '0', so there is always room below any rank.
+fn rank_between(lo: &str, hi: Option<&str>) -> String {
+    let Some(hi) = hi else {
+        return rank_after(lo);
+    };
+    let (lo, hi) = (lo.as_bytes(), hi.as_bytes());
+    let base = RANK_DIGITS.len();
+    let mut out = String::new();
+    let mut bounded = true;
+    let mut i = 0;
+    loop {
+        let l = lo.get(i).map(|&c| rank_digit(c)).unwrap_or(0);
+        let h = if bounded { hi.get(i).map(|&c| rank_digit(c)).unwrap_or(base) } else { base };
+        if h > l + 1 {
+            out.push(RANK_DIGITS[(l + h) / 2] as char);
+            return out;
+        }
+        out.push(RANK_DIGITS[l] as char);
+        if h > l {
+            bounded = false;
+        }
+        i += 1;
+    }
+}
+
+/// Deadlines were free text before they had to be dates. A value that is not a date is moved
+/// into the task's notes, since what "Friday" meant depends on when it was written; otherwise
+/// every later save of the task would fail validation or re-resolve it against today.
+async fn migrate_legacy_deadlines(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let legacy: Vec<(i64, String, Option<String>)> = sqlx::query_as(
+        "SELECT id, deadline, notes FROM tasks \
+         WHERE deadline IS NOT NULL AND NOT (deadline GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]' AND date(deadline) = deadline)",
+    )
+    .fetch_all(pool)
+    .await?;
+    for (id, deadline, notes) in legacy {
+        let deadline = deadline.trim();
+        if is_iso_date(deadline) {
+            sqlx::query("UPDATE tasks SET deadline=? WHERE id=?")
+                .bind(deadline)
+                .bind(id)
+                .execute(pool)
+                .await?;
+            continue;
+        }
+        let mut notes = match notes.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            Some(notes) => format!("Deadline: {}\n\n{}", deadline, notes),
+            None => format!("Deadline: {}", deadline),
+        };
+        if notes.len() > 2000 {
+            let mut end = 2000;
+            while !notes.is_char_boundary(end) {
+                end -= 1;
+            }
+            notes.truncate(end);
+        }
+        sqlx::query("UPDATE tasks SET deadline=NULL, notes=? WHERE id=?")
+            .bind(&notes)
+            .bind(id)
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
 }
 
 async fn init_db(pool: &SqlitePool) -> Result<(), sqlx::Error> {
@@ -87,6 +339,211 @@ async fn init_db(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS transition_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            project_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            from_status TEXT,
+            to_status TEXT
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS task_status_changes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_id INTEGER NOT NULL,
+            from_status TEXT NOT NULL,
+            to_status TEXT NOT NULL,
+            comment TEXT,
+            changed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS wip_limits (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            project_id INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            scope TEXT NOT NULL DEFAULT 'project',
+            max_tasks INTEGER NOT NULL,
+            mode TEXT NOT NULL DEFAULT 'reject'
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS task_drafts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            meeting_notes TEXT NOT NULL,
+            tasks TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    let _ = sqlx::query("ALTER TABLE task_drafts ADD COLUMN rejected TEXT")
+        .execute(pool)
+        .await;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS meetings (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            date TEXT NOT NULL,
+            attendees TEXT NOT NULL DEFAULT '[]',
+            notes TEXT NOT NULL,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'queued',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            max_attempts INTEGER NOT NULL DEFAULT 3,
+            completed INTEGER NOT NULL DEFAULT 0,
+            total INTEGER NOT NULL DEFAULT 0,
+            result TEXT,
+            error TEXT,
+            run_after TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    let _ = sqlx::query("ALTER TABLE tasks ADD COLUMN meeting_id INTEGER")
+        .execute(pool)
+        .await;
+
+    let _ = sqlx::query("ALTER TABLE tasks ADD COLUMN prompt_version INTEGER")
+        .execute(pool)
+        .await;
+
+    let _ = sqlx::query("ALTER TABLE meetings ADD COLUMN language TEXT")
+        .execute(pool)
+        .await;
+
+    let _ = sqlx::query("ALTER TABLE tasks ADD COLUMN parent_id INTEGER")
+        .execute(pool)
+        .await;
+
+    let _ = sqlx::query("ALTER TABLE tasks ADD COLUMN estimate_hours REAL")
+        .execute(pool)
+        .await;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS prompt_templates (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            version INTEGER NOT NULL,
+            template TEXT NOT NULL,
+            conventions TEXT NOT NULL DEFAULT '',
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (name, version)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS ai_usage (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            day TEXT NOT NULL,
+            user TEXT NOT NULL,
+            operation TEXT NOT NULL,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            job_id INTEGER,
+            prompt_tokens INTEGER NOT NULL DEFAULT 0,
+            completion_tokens INTEGER NOT NULL DEFAULT 0,
+            cost_usd REAL,
+            latency_ms INTEGER NOT NULL,
+            outcome TEXT NOT NULL,
+            error TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_ai_usage_day ON ai_usage (day)")
+        .execute(pool)
+        .await?;
+
+    // Version 1 of every template was seeded from the built-in text.
+    let _ = sqlx::query("ALTER TABLE prompt_templates ADD COLUMN builtin INTEGER NOT NULL DEFAULT 0")
+        .execute(pool)
+        .await;
+    sqlx::query("UPDATE prompt_templates SET builtin=1 WHERE version=1")
+        .execute(pool)
+        .await?;
+    seed_prompt_templates(pool).await?;
+
+    let _ = sqlx::query("ALTER TABLE task_drafts ADD COLUMN meeting_id INTEGER")
+        .execute(pool)
+        .await;
+
+    let _ = sqlx::query("ALTER TABLE task_drafts ADD COLUMN names TEXT")
+        .execute(pool)
+        .await;
+
+    let _ = sqlx::query("ALTER TABLE task_drafts ADD COLUMN deadlines TEXT")
+        .execute(pool)
+        .await;
+
+    let _ = sqlx::query("ALTER TABLE assignees ADD COLUMN aliases TEXT NOT NULL DEFAULT '[]'")
+        .execute(pool)
+        .await;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS task_embeddings (
+            task_id INTEGER PRIMARY KEY,
+            model TEXT NOT NULL,
+            content TEXT NOT NULL,
+            embedding TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    // Embeddings of tasks deleted before deletes removed them.
+    sqlx::query("DELETE FROM task_embeddings WHERE task_id NOT IN (SELECT id FROM tasks)")
+        .execute(pool)
+        .await?;
+
     sqlx::query("INSERT OR IGNORE INTO projects (id, name) VALUES (1, 'General')")
         .execute(pool)
         .await?;
@@ -116,6 +573,104 @@ async fn init_db(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await;
 
+    let _ = sqlx::query("ALTER TABLE tasks ADD COLUMN rank TEXT")
+        .execute(pool)
+        .await;
+
+    let _ = sqlx::query("ALTER TABLE tasks ADD COLUMN key TEXT")
+        .execute(pool)
+        .await;
+
+    let _ = sqlx::query("ALTER TABLE projects ADD COLUMN key TEXT")
+        .execute(pool)
+        .await;
+
+    let _ = sqlx::query("ALTER TABLE projects ADD COLUMN next_number INTEGER NOT NULL DEFAULT 1")
+        .execute(pool)
+        .await;
+
+    // Needs the `notes` column, which older databases only get from the statements above.
+    sqlx::query("UPDATE tasks SET deadline = NULL WHERE trim(deadline) = ''")
+        .execute(pool)
+        .await?;
+    migrate_legacy_deadlines(pool).await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS task_key_aliases (
+            key TEXT PRIMARY KEY,
+            task_id INTEGER NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    let unkeyed: Vec<(i64, String)> = sqlx::query_as("SELECT id, name FROM projects WHERE key IS NULL ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+    for (id, name) in unkeyed {
+        let key = unique_project_key(pool, &derive_project_key(&name)).await?;
+        sqlx::query("UPDATE projects SET key=? WHERE id=?")
+            .bind(&key)
+            .bind(id)
+            .execute(pool)
+            .await?;
+    }
+
+    let unkeyed: Vec<(i64, String)> = sqlx::query_as("SELECT id, project FROM tasks WHERE key IS NULL ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+    for (id, project) in unkeyed {
+        let mut conn = pool.acquire().await?;
+        if let Some(key) = allocate_task_key(&mut conn, &project).await? {
+            sqlx::query("UPDATE tasks SET key=? WHERE id=?")
+                .bind(&key)
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_projects_key ON projects (key)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_tasks_key ON tasks (key)")
+        .execute(pool)
+        .await?;
+
+    let unranked: Vec<(i64,)> = sqlx::query_as("SELECT id FROM tasks WHERE rank IS NULL OR rank = '' ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+    let mut rank: String = sqlx::query_scalar("SELECT COALESCE(MAX(rank), '') FROM tasks")
+        .fetch_one(pool)
+        .await?;
+    for (id,) in unranked {
+        rank = rank_after(&rank);
+        sqlx::query("UPDATE tasks SET rank=? WHERE id=?")
+            .bind(&rank)
+            .bind(id)
+            .execute(pool)
+            .await?;
+    }
+
+    // Status changes used to keep the old rank, leaving ties within a column.
+    let tied: Vec<(i64, String)> = sqlx::query_as(
+        "SELECT id, status FROM tasks t WHERE EXISTS \
+         (SELECT 1 FROM tasks o WHERE o.status = t.status AND o.rank = t.rank AND o.id < t.id) ORDER BY id",
+    )
+    .fetch_all(pool)
+    .await?;
+    for (id, status) in tied {
+        let rank = next_rank(pool, &status).await?;
+        sqlx::query("UPDATE tasks SET rank=? WHERE id=?")
+            .bind(&rank)
+            .bind(id)
+            .execute(pool)
+            .await?;
+    }
+
     Ok(())
 }
 
@@ -123,16 +678,20 @@ async fn init_db(pool: &SqlitePool) -> Result<(), sqlx::Error> {
 struct Project {
     id: i64,
     name: String,
+    key: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
 struct Assignee {
     id: i64,
     name: String,
+    /// Other names the person goes by in meeting notes (e.g. "Bob" for "Robert Smith").
+    #[sqlx(json)]
+    aliases: Vec<String>,
 }
 
 async fn get_projects(data: web::Data<AppState>) -> Result<HttpResponse> {
-    let projects = sqlx::query_as::<_, Project>("SELECT id, name FROM projects ORDER BY name")
+    let projects = sqlx::query_as::<_, Project>("SELECT id, name, key FROM projects ORDER BY name")
         .fetch_all(&data.pool)
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
@@ -142,6 +701,65 @@ async fn get_projects(data: web::Data<AppState>) -> Result<HttpResponse> {
 #[derive(Deserialize)]
 struct CreateProjectRequest {
     name: String,
+    #[serde(default)]
+    key: Option<String>,
+}
+
+/// Derives a short key from a project name: initials of multi-word names, otherwise the first letters.
+fn derive_project_key(name: &str) -> String {
+    let words: Vec<&str> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let key: String = if words.len() > 1 {
+        words.iter().take(4).filter_map(|w| w.chars().next()).collect()
+    } else {
+        words.first().map(|w| w.chars().take(3).collect()).unwrap_or_default()
+    };
+    let key = key.to_ascii_uppercase();
+    if key.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        key
+    } else {
+        format!("P{}", key)
+    }
+}
+
+fn validate_project_key(key: &str) -> Option<&'static str> {
+    let mut chars = key.chars();
+    if !chars.next().is_some_and(|c| c.is_ascii_uppercase())
+        || key.len() > 10
+        || !chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    {
+        return Some("key must be 1-10 uppercase letters or digits, starting with a letter");
+    }
+    None
+}
+
+/// Appends a number to `base` until no other project uses it.
+async fn unique_project_key(pool: &SqlitePool, base: &str) -> Result<String, sqlx::Error> {
+    let mut key = base.to_string();
+    let mut n = 2;
+    while sqlx::query_scalar::<_, i64>("SELECT 1 FROM projects WHERE key=?")
+        .bind(&key)
+        .fetch_optional(pool)
+        .await?
+        .is_some()
+    {
+        key = format!("{}{}", base, n);
+        n += 1;
+    }
+    Ok(key)
+}
+
+/// Takes the next sequential key (e.g. `BE-42`) from the project's counter.
+async fn allocate_task_key(conn: &mut SqliteConnection, project: &str) -> Result<Option<String>, sqlx::Error> {
+    let row: Option<(String, i64)> = sqlx::query_as(
+        "UPDATE projects SET next_number = next_number + 1 WHERE name=? AND key IS NOT NULL RETURNING key, next_number - 1",
+    )
+    .bind(project)
+    .fetch_optional(conn)
+    .await?;
+    Ok(row.map(|(key, n)| format!("{}-{}", key, n)))
 }
 
 async fn create_project(
@@ -152,12 +770,42 @@ async fn create_project(
     if name.is_empty() {
         return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "name must not be empty" })));
     }
-    let id = sqlx::query_scalar::<_, i64>("INSERT INTO projects (name) VALUES (?) RETURNING id")
+    let key = match body.key.as_deref().map(str::trim).filter(|k| !k.is_empty()) {
+        Some(key) => {
+            let key = key.to_ascii_uppercase();
+            if let Some(msg) = validate_project_key(&key) {
+                return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+            }
+            key
+        }
+        None => unique_project_key(&data.pool, &derive_project_key(name))
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?,
+    };
+    // Continue numbering after any keys left behind by a deleted project with the same key.
+    let next_number: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(MAX(CAST(substr(key, length(?) + 2) AS INTEGER)), 0) + 1
+        FROM (SELECT key FROM tasks UNION ALL SELECT key FROM task_key_aliases)
+        WHERE key LIKE ? || '-%'
+        "#,
+    )
+    .bind(&key)
+    .bind(&key)
+    .fetch_one(&data.pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    let id = sqlx::query_scalar::<_, i64>("INSERT INTO projects (name, key, next_number) VALUES (?, ?, ?) RETURNING id")
         .bind(name)
+        .bind(&key)
+        .bind(next_number)
         .fetch_one(&data.pool)
         .await
         .map_err(|e| {
             if let sqlx::Error::Database(db) = &e {
+                if db.message().contains("projects.key") {
+                    return actix_web::error::ErrorBadRequest("project key already exists");
+                }
                 if db.message().contains("UNIQUE") {
                     return actix_web::error::ErrorBadRequest("project already exists");
                 }
@@ -167,6 +815,7 @@ async fn create_project(
     let project = Project {
         id,
         name: name.to_string(),
+        key,
     };
     Ok(HttpResponse::Created().json(project))
 }
@@ -187,11 +836,303 @@ async fn delete_project(
     if result.rows_affected() == 0 {
         return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "project not found" })));
     }
-    Ok(HttpResponse::NoContent().finish())
-}
-
+    sqlx::query("DELETE FROM transition_rules WHERE project_id=?")
+        .bind(id)
+        .execute(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    sqlx::query("DELETE FROM wip_limits WHERE project_id=?")
+        .bind(id)
+        .execute(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
+struct TransitionRule {
+    kind: String,
+    #[serde(default)]
+    from_status: Option<String>,
+    #[serde(default)]
+    to_status: Option<String>,
+}
+
+impl TransitionRule {
+    fn matches(&self, from: &str, to: &str) -> bool {
+        !matches!(&self.from_status, Some(s) if s != from)
+            && !matches!(&self.to_status, Some(s) if s != to)
+    }
+
+    /// Returns the failure message when `task` (already in its new state), moving from `from`,
+    /// violates this rule.
+    fn violation(&self, from: &str, task: &Task, comment: Option<&str>) -> Option<String> {
+        match self.kind.as_str() {
+            "require_notes" if task.notes.as_deref().unwrap_or("").trim().is_empty() => {
+                Some(format!("moving to '{}' requires a reason in notes", task.status))
+            }
+            "require_assignee" if task.assignee == "Unassigned" => {
+                Some(format!("moving to '{}' requires an assignee", task.status))
+            }
+            "require_comment" if comment.unwrap_or("").trim().is_empty() => Some(format!(
+                "moving from '{}' to '{}' requires a comment",
+                from, task.status
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn validate_transition_rule(rule: &TransitionRule) -> Option<&'static str> {
+    if !RULE_KINDS.contains(&rule.kind.as_str()) {
+        return Some("kind must be one of: require_notes, require_assignee, require_comment");
+    }
+    for status in [&rule.from_status, &rule.to_status].into_iter().flatten() {
+        if !VALID_STATUSES.contains(&status.as_str()) {
+            return Some("from_status and to_status must be one of: todo, in_progress, done, blocked");
+        }
+    }
+    None
+}
+
+async fn get_transition_rules(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let rules = sqlx::query_as::<_, TransitionRule>(
+        "SELECT kind, from_status, to_status FROM transition_rules WHERE project_id=? ORDER BY id",
+    )
+    .bind(id)
+    .fetch_all(&data.pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+async fn set_transition_rules(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: web::Json<Vec<TransitionRule>>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let rules = body.into_inner();
+    for rule in &rules {
+        if let Some(msg) = validate_transition_rule(rule) {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+        }
+    }
+
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let project: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM projects WHERE id=?")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if project.is_none() {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "project not found" })));
+    }
+    sqlx::query("DELETE FROM transition_rules WHERE project_id=?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    for rule in &rules {
+        sqlx::query("INSERT INTO transition_rules (project_id, kind, from_status, to_status) VALUES (?, ?, ?, ?)")
+            .bind(id)
+            .bind(&rule.kind)
+            .bind(&rule.from_status)
+            .bind(&rule.to_status)
+            .execute(&mut *tx)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+fn default_wip_scope() -> String {
+    "project".to_string()
+}
+
+fn default_wip_mode() -> String {
+    "reject".to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
+struct WipLimit {
+    status: String,
+    #[serde(default = "default_wip_scope")]
+    scope: String,
+    max_tasks: i64,
+    #[serde(default = "default_wip_mode")]
+    mode: String,
+}
+
+fn validate_wip_limit(limit: &WipLimit) -> Option<&'static str> {
+    if !VALID_STATUSES.contains(&limit.status.as_str()) {
+        return Some("status must be one of: todo, in_progress, done, blocked");
+    }
+    if !WIP_SCOPES.contains(&limit.scope.as_str()) {
+        return Some("scope must be one of: project, assignee");
+    }
+    if !WIP_MODES.contains(&limit.mode.as_str()) {
+        return Some("mode must be one of: reject, warn");
+    }
+    if limit.max_tasks < 1 {
+        return Some("max_tasks must be at least 1");
+    }
+    None
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct WipCount {
+    assignee: String,
+    current: i64,
+}
+
+#[derive(Serialize)]
+struct WipUsage {
+    project: String,
+    #[serde(flatten)]
+    limit: WipLimit,
+    current: i64,
+    over_limit: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    assignees: Vec<WipCount>,
+}
+
+/// Current task counts against every WIP limit, optionally restricted to one project.
+/// For per-assignee limits `current` is the highest count of any single assignee.
+async fn wip_usage(pool: &SqlitePool, project_id: Option<i64>) -> Result<Vec<WipUsage>, sqlx::Error> {
+    #[derive(sqlx::FromRow)]
+    struct Row {
+        project: String,
+        status: String,
+        scope: String,
+        max_tasks: i64,
+        mode: String,
+    }
+    let rows = sqlx::query_as::<_, Row>(
+        r#"
+        SELECT p.name AS project, w.status, w.scope, w.max_tasks, w.mode
+        FROM wip_limits w JOIN projects p ON p.id = w.project_id
+        WHERE ? IS NULL OR w.project_id = ?
+        ORDER BY p.name, w.id
+        "#,
+    )
+    .bind(project_id)
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut usage = Vec::new();
+    for row in rows {
+        let limit = WipLimit {
+            status: row.status,
+            scope: row.scope,
+            max_tasks: row.max_tasks,
+            mode: row.mode,
+        };
+        let (current, assignees) = if limit.scope == "assignee" {
+            let assignees = sqlx::query_as::<_, WipCount>(
+                r#"
+                SELECT assignee, COUNT(*) AS current FROM tasks
+                WHERE project=? AND status=? AND COALESCE(archived, 0)=0 AND assignee != 'Unassigned'
+                GROUP BY assignee ORDER BY current DESC, assignee
+                "#,
+            )
+            .bind(&row.project)
+            .bind(&limit.status)
+            .fetch_all(pool)
+            .await?;
+            (assignees.first().map(|a| a.current).unwrap_or(0), assignees)
+        } else {
+            let current: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM tasks WHERE project=? AND status=? AND COALESCE(archived, 0)=0",
+            )
+            .bind(&row.project)
+            .bind(&limit.status)
+            .fetch_one(pool)
+            .await?;
+            (current, Vec::new())
+        };
+        usage.push(WipUsage {
+            project: row.project,
+            over_limit: current > limit.max_tasks,
+            current,
+            limit,
+            assignees,
+        });
+    }
+    Ok(usage)
+}
+
+async fn get_wip_usage(data: web::Data<AppState>) -> Result<HttpResponse> {
+    let usage = wip_usage(&data.pool, None)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(usage))
+}
+
+async fn get_wip_limits(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse> {
+    let usage = wip_usage(&data.pool, Some(path.into_inner()))
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(usage))
+}
+
+async fn set_wip_limits(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: web::Json<Vec<WipLimit>>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let limits = body.into_inner();
+    for limit in &limits {
+        if let Some(msg) = validate_wip_limit(limit) {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+        }
+    }
+
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let project: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM projects WHERE id=?")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if project.is_none() {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "project not found" })));
+    }
+    sqlx::query("DELETE FROM wip_limits WHERE project_id=?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    for limit in &limits {
+        sqlx::query("INSERT INTO wip_limits (project_id, status, scope, max_tasks, mode) VALUES (?, ?, ?, ?, ?)")
+            .bind(id)
+            .bind(&limit.status)
+            .bind(&limit.scope)
+            .bind(limit.max_tasks)
+            .bind(&limit.mode)
+            .execute(&mut *tx)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let usage = wip_usage(&data.pool, Some(id))
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(usage))
+}
+
 async fn get_assignees(data: web::Data<AppState>) -> Result<HttpResponse> {
-    let assignees = sqlx::query_as::<_, Assignee>("SELECT id, name FROM assignees ORDER BY name")
+    let assignees = sqlx::query_as::<_, Assignee>("SELECT id, name, aliases FROM assignees ORDER BY name")
         .fetch_all(&data.pool)
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
@@ -201,6 +1142,20 @@ async fn get_assignees(data: web::Data<AppState>) -> Result<HttpResponse> {
 #[derive(Deserialize)]
 struct CreateAssigneeRequest {
     name: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+/// Trims aliases and drops empty and repeated ones, ignoring case.
+fn normalize_aliases(aliases: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for alias in aliases {
+        let alias = alias.trim();
+        if !alias.is_empty() && !out.iter().any(|a| a.eq_ignore_ascii_case(alias)) {
+            out.push(alias.to_string());
+        }
+    }
+    out
 }
 
 async fn create_assignee(
@@ -211,8 +1166,11 @@ async fn create_assignee(
     if name.is_empty() {
         return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "name must not be empty" })));
     }
-    let id = sqlx::query_scalar::<_, i64>("INSERT INTO assignees (name) VALUES (?) RETURNING id")
+    let aliases = normalize_aliases(body.aliases.clone());
+    let aliases_json = serde_json::to_string(&aliases).map_err(actix_web::error::ErrorInternalServerError)?;
+    let id = sqlx::query_scalar::<_, i64>("INSERT INTO assignees (name, aliases) VALUES (?, ?) RETURNING id")
         .bind(name)
+        .bind(&aliases_json)
         .fetch_one(&data.pool)
         .await
         .map_err(|e| {
@@ -226,10 +1184,36 @@ async fn create_assignee(
     let assignee = Assignee {
         id,
         name: name.to_string(),
+        aliases,
     };
     Ok(HttpResponse::Created().json(assignee))
 }
 
+#[derive(Deserialize)]
+struct UpdateAliasesRequest {
+    aliases: Vec<String>,
+}
+
+/// Replaces the aliases used to match an assignee in AI-extracted tasks.
+async fn update_assignee_aliases(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: web::Json<UpdateAliasesRequest>,
+) -> Result<HttpResponse> {
+    let aliases = normalize_aliases(body.into_inner().aliases);
+    let aliases_json = serde_json::to_string(&aliases).map_err(actix_web::error::ErrorInternalServerError)?;
+    let assignee = sqlx::query_as::<_, Assignee>("UPDATE assignees SET aliases=? WHERE id=? RETURNING id, name, aliases")
+        .bind(&aliases_json)
+        .bind(path.into_inner())
+        .fetch_optional(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    match assignee {
+        Some(assignee) => Ok(HttpResponse::Ok().json(assignee)),
+        None => Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "assignee not found" }))),
+    }
+}
+
 async fn delete_assignee(
     data: web::Data<AppState>,
     path: web::Path<i64>,
@@ -249,6 +1233,212 @@ async fn delete_assignee(
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Settings for turning relative deadlines ("by Friday") into dates, read at startup.
+#[derive(Clone, Copy)]
+struct DeadlineConfig {
+    /// `APP_TIMEZONE` (an IANA name, default `UTC`): decides which date "today" is.
+    timezone: chrono_tz::Tz,
+    /// `WEEK_START` (default `monday`): first day of "this week" and "next week".
+    week_start: Weekday,
+    /// `WEEK_END` (default `friday`): last working day of the week, what "end of week" means.
+    week_end: Weekday,
+    /// `SPRINT_START_DATE`: the first day of any sprint; "end of sprint" needs it.
+    sprint_start: Option<NaiveDate>,
+    /// `SPRINT_LENGTH_DAYS` (default 14).
+    sprint_length_days: u64,
+}
+
+impl DeadlineConfig {
+    fn from_env() -> Self {
+        let timezone = match std::env::var("APP_TIMEZONE") {
+            Ok(tz) if !tz.is_empty() => tz
+                .parse()
+                .unwrap_or_else(|_| panic!("APP_TIMEZONE must be an IANA time zone such as Europe/Berlin, got '{}'", tz)),
+            _ => chrono_tz::UTC,
+        };
+        let weekday = |var: &str, default: Weekday| match std::env::var(var) {
+            Ok(day) if !day.is_empty() => parse_weekday(&day.to_lowercase())
+                .unwrap_or_else(|| panic!("{} must be a day of the week, got '{}'", var, day)),
+            _ => default,
+        };
+        DeadlineConfig {
+            timezone,
+            week_start: weekday("WEEK_START", Weekday::Mon),
+            week_end: weekday("WEEK_END", Weekday::Fri),
+            sprint_start: std::env::var("SPRINT_START_DATE")
+                .ok()
+                .and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok()),
+            sprint_length_days: std::env::var("SPRINT_LENGTH_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(14),
+        }
+    }
+
+    fn today(&self) -> NaiveDate {
+        chrono::Utc::now().with_timezone(&self.timezone).date_naive()
+    }
+
+    /// First day of the week containing `date`.
+    fn week_of(&self, date: NaiveDate) -> NaiveDate {
+        date - Days::new(date.weekday().days_since(self.week_start) as u64)
+    }
+
+    /// Last day of the sprint containing `date`, `offset` sprints later.
+    fn sprint_end(&self, date: NaiveDate, offset: u64) -> Option<NaiveDate> {
+        let start = self.sprint_start?;
+        let elapsed = (date - start).num_days().div_euclid(self.sprint_length_days as i64);
+        let current = if elapsed >= 0 {
+            start + Days::new(elapsed as u64 * self.sprint_length_days)
+        } else {
+            start - Days::new(elapsed.unsigned_abs() * self.sprint_length_days)
+        };
+        current.checked_add_days(Days::new((offset + 1) * self.sprint_length_days - 1))
+    }
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    Some(match word {
+        "monday" | "mon" => Weekday::Mon,
+        "tuesday" | "tue" | "tues" => Weekday::Tue,
+        "wednesday" | "wed" => Weekday::Wed,
+        "thursday" | "thu" | "thur" | "thurs" => Weekday::Thu,
+        "friday" | "fri" => Weekday::Fri,
+        "saturday" | "sat" => Weekday::Sat,
+        "sunday" | "sun" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+fn parse_month(word: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november",
+        "december",
+    ];
+    MONTHS
+        .iter()
+        .position(|m| *m == word || (word.len() >= 3 && m.starts_with(word)))
+        .map(|i| i as u32 + 1)
+}
+
+fn parse_count(word: &str) -> Option<u64> {
+    const WORDS: [&str; 12] = [
+        "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven", "twelve",
+    ];
+    match word {
+        "a" | "an" => Some(1),
+        _ => word
+            .parse()
+            .ok()
+            .or_else(|| WORDS.iter().position(|w| *w == word).map(|i| i as u64 + 1)),
+    }
+}
+
+/// Resolves a deadline given as a date or as a relative expression against `reference`
+/// (the meeting date, or today). Understands `YYYY-MM-DD`, today/tomorrow, weekdays
+/// ("by Friday" is the next Friday on or after `reference`, "next Friday" the Friday of
+/// the following week), "in 3 days"/"in two weeks"/"in a month", "end of (next) week"
+/// (the fifth day from the week start; the next one once it has passed), "end of (next)
+/// month", "end of (next) sprint", and month-day dates such as "March 5th" (next year once
+/// the date has passed).
+fn resolve_deadline(text: &str, reference: NaiveDate, config: &DeadlineConfig) -> Option<NaiveDate> {
+    let text = text.trim();
+    if is_iso_date(text) {
+        return NaiveDate::parse_from_str(text, "%Y-%m-%d").ok();
+    }
+    let lowered = text.to_lowercase();
+    let words: Vec<&str> = lowered
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| {
+            !w.is_empty()
+                && !matches!(
+                    *w,
+                    "by" | "on" | "before" | "due" | "until" | "till" | "the" | "of" | "at" | "latest" | "no" | "later" | "than"
+                )
+        })
+        .collect();
+    let days = |n: u64| reference.checked_add_days(Days::new(n));
+    let week_end = |weeks: u64| {
+        let offset = config.week_end.days_since(config.week_start) as u64;
+        config.week_of(reference).checked_add_days(Days::new(7 * weeks + offset))
+    };
+    let month_end = |months: u32| {
+        let first = reference.with_day(1)?.checked_add_months(Months::new(months + 1))?;
+        first.pred_opt()
+    };
+
+    match words.as_slice() {
+        ["today"] | ["tonight"] | ["eod"] | ["end", "day"] => Some(reference),
+        ["tomorrow"] => days(1),
+        ["day", "after", "tomorrow"] => days(2),
+        ["eow"] | ["end", "week"] | ["end", "this", "week"] | ["this", "week"] => {
+            week_end(0).filter(|end| *end >= reference).or_else(|| week_end(1))
+        }
+        ["end", "next", "week"] | ["next", "week"] => week_end(1),
+        ["eom"] | ["end", "month"] | ["end", "this", "month"] | ["this", "month"] => month_end(0),
+        ["end", "next", "month"] | ["next", "month"] => month_end(1),
+        ["end", "sprint"] | ["end", "this", "sprint"] | ["this", "sprint"] => config.sprint_end(reference, 0),
+        ["end", "next", "sprint"] | ["next", "sprint"] => config.sprint_end(reference, 1),
+        ["in", unit] => in_units(reference, 1, unit),
+        ["in", count, unit] => in_units(reference, parse_count(count)?, unit),
+        [day] | ["this", day] => {
+            let day = parse_weekday(day)?;
+            days(day.days_since(reference.weekday()) as u64)
+        }
+        ["next", day] => {
+            let day = parse_weekday(day)?;
+            config
+                .week_of(reference)
+                .checked_add_days(Days::new(7 + day.days_since(config.week_start) as u64))
+        }
+        [first, second, rest @ ..] if rest.len() <= 1 => {
+            let (month, day) = match (parse_month(first), parse_month(second)) {
+                (Some(month), None) => (month, *second),
+                (None, Some(month)) => (month, *first),
+                _ => return None,
+            };
+            let day: u32 = day.trim_end_matches(|c: char| c.is_ascii_alphabetic()).parse().ok()?;
+            match rest {
+                [year] => NaiveDate::from_ymd_opt(year.parse().ok()?, month, day),
+                _ => {
+                    let date = NaiveDate::from_ymd_opt(reference.year(), month, day)?;
+                    if date < reference {
+                        NaiveDate::from_ymd_opt(reference.year() + 1, month, day)
+                    } else {
+                        Some(date)
+                    }
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+fn in_units(reference: NaiveDate, count: u64, unit: &str) -> Option<NaiveDate> {
+    match unit.trim_end_matches('s') {
+        "day" => reference.checked_add_days(Days::new(count)),
+        "week" => reference.checked_add_days(Days::new(7 * count)),
+        "month" => reference.checked_add_months(Months::new(u32::try_from(count).ok()?)),
+        _ => None,
+    }
+}
+
+/// Replaces a relative deadline on a task being saved with the date it stands for
+/// (relative to today) and drops an empty one. Unresolvable values are left for
+/// `validate_task` to reject.
+fn resolve_task_deadline(task: &mut Task, config: &DeadlineConfig) {
+    task.deadline = match task.deadline.take().map(|d| d.trim().to_string()) {
+        Some(deadline) if deadline.is_empty() => None,
+        Some(deadline) => Some(
+            resolve_deadline(&deadline, config.today(), config)
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or(deadline),
+        ),
+        None => None,
+    };
+}
+
 fn validate_task(task: &Task) -> Option<&'static str> {
     if task.title.trim().is_empty() {
         return Some("title must not be empty");
@@ -276,11 +1466,17 @@ fn validate_task(task: &Task) -> Option<&'static str> {
     if task.assignee.trim().is_empty() {
         return Some("assignee must not be empty");
     }
+    if task.deadline.as_deref().is_some_and(|d| !is_iso_date(d)) {
+        return Some("deadline must be a valid YYYY-MM-DD date or a relative date such as \"next friday\"");
+    }
     if let Some(ref notes) = task.notes {
         if notes.len() > 2000 {
             return Some("notes must be at most 2000 characters");
         }
     }
+    if task.estimate_hours.is_some_and(|h| !(0.0..=10000.0).contains(&h)) {
+        return Some("estimate_hours must be between 0 and 10000");
+    }
     None
 }
 
@@ -300,151 +1496,243 @@ async fn assignee_exists(pool: &SqlitePool, name: &str) -> Result<bool, sqlx::Er
     Ok(row.is_some())
 }
 
-async fn get_tasks(data: web::Data<AppState>) -> Result<HttpResponse> {
-    let rows = sqlx::query_as::<_, TaskRow>(
-        "SELECT id, title, description, tags, deadline, project, assignee, status, in_sprint, notes, archived FROM tasks ORDER BY id",
-    )
-    .fetch_all(&data.pool)
-    .await
-    .map_err(actix_web::error::ErrorInternalServerError)?;
+#[derive(Serialize)]
+struct TransitionViolation {
+    error: String,
+    rule: TransitionRule,
+    from_status: String,
+    to_status: String,
+}
 
-    let tasks: Vec<Task> = rows
-        .into_iter()
-        .map(|r| r.into_task())
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+/// Checks the project's transition rules for a status change from `from` to `task.status`.
+/// Returns the first rule that fails.
+async fn check_transition(
+    conn: &mut SqliteConnection,
+    from: &str,
+    task: &Task,
+    comment: Option<&str>,
+) -> Result<Option<TransitionViolation>, sqlx::Error> {
+    if from == task.status {
+        return Ok(None);
+    }
+    let rules = sqlx::query_as::<_, TransitionRule>(
+        r#"
+        SELECT r.kind, r.from_status, r.to_status
+        FROM transition_rules r JOIN projects p ON p.id = r.project_id
+        WHERE p.name = ?
+        ORDER BY r.id
+        "#,
+    )
+    .bind(&task.project)
+    .fetch_all(&mut *conn)
+    .await?;
 
-    Ok(HttpResponse::Ok().json(tasks))
+    for rule in rules.into_iter().filter(|r| r.matches(from, &task.status)) {
+        if let Some(error) = rule.violation(from, task, comment) {
+            return Ok(Some(TransitionViolation {
+                error,
+                rule,
+                from_status: from.to_string(),
+                to_status: task.status.clone(),
+            }));
+        }
+    }
+    Ok(None)
 }
 
-#[derive(sqlx::FromRow)]
-struct TaskRow {
-    id: i64,
-    title: String,
-    description: String,
-    tags: String,
-    deadline: Option<String>,
-    project: String,
-    assignee: String,
-    status: String,
-    in_sprint: i32,
-    notes: Option<String>,
-    archived: Option<i32>,
+#[derive(Serialize)]
+struct WipExceeded {
+    error: String,
+    limit: WipLimit,
+    current: i64,
 }
 
-impl TaskRow {
-    fn into_task(self) -> Result<Task, serde_json::Error> {
-        let tags: Vec<String> = if self.tags.is_empty() {
-            Vec::new()
+/// Finds the WIP limits that `task` would exceed by entering its status column.
+/// `before` is the task's previous state; limits it already counted towards are skipped.
+async fn check_wip(
+    conn: &mut SqliteConnection,
+    before: Option<&Task>,
+    task: &Task,
+) -> Result<Vec<WipExceeded>, sqlx::Error> {
+    if task.archived {
+        return Ok(Vec::new());
+    }
+    let limits = sqlx::query_as::<_, WipLimit>(
+        r#"
+        SELECT w.status, w.scope, w.max_tasks, w.mode
+        FROM wip_limits w JOIN projects p ON p.id = w.project_id
+        WHERE p.name = ? AND w.status = ?
+        ORDER BY w.id
+        "#,
+    )
+    .bind(&task.project)
+    .bind(&task.status)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let same_column = before.is_some_and(|b| !b.archived && b.status == task.status && b.project == task.project);
+    let mut exceeded = Vec::new();
+    for limit in limits {
+        let current: i64 = if limit.scope == "assignee" {
+            if task.assignee == "Unassigned" || (same_column && before.is_some_and(|b| b.assignee == task.assignee)) {
+                continue;
+            }
+            sqlx::query_scalar(
+                "SELECT COUNT(*) FROM tasks WHERE project=? AND status=? AND assignee=? AND COALESCE(archived, 0)=0 AND id!=?",
+            )
+            .bind(&task.project)
+            .bind(&task.status)
+            .bind(&task.assignee)
+            .bind(task.id)
+            .fetch_one(&mut *conn)
+            .await?
         } else {
-            serde_json::from_str(&self.tags)?
+            if same_column {
+                continue;
+            }
+            sqlx::query_scalar(
+                "SELECT COUNT(*) FROM tasks WHERE project=? AND status=? AND COALESCE(archived, 0)=0 AND id!=?",
+            )
+            .bind(&task.project)
+            .bind(&task.status)
+            .bind(task.id)
+            .fetch_one(&mut *conn)
+            .await?
         };
-        Ok(Task {
-            id: self.id,
-            title: self.title,
-            description: self.description,
-            tags,
-            deadline: self.deadline,
-            project: self.project,
-            assignee: self.assignee,
-            status: self.status,
-            in_sprint: self.in_sprint != 0,
-            notes: self.notes.filter(|s| !s.is_empty()),
-            archived: self.archived.unwrap_or(0) != 0,
-        })
+        if current >= limit.max_tasks {
+            let error = if limit.scope == "assignee" {
+                format!(
+                    "{} already has {} '{}' tasks in {} (limit {})",
+                    task.assignee, current, task.status, task.project, limit.max_tasks
+                )
+            } else {
+                format!(
+                    "{} already has {} '{}' tasks (limit {})",
+                    task.project, current, task.status, limit.max_tasks
+                )
+            };
+            exceeded.push(WipExceeded { error, limit, current });
+        }
     }
+    Ok(exceeded)
 }
 
-async fn create_task(
-    data: web::Data<AppState>,
-    task: web::Json<Task>,
-) -> Result<HttpResponse> {
-    let mut task_inner = task.into_inner();
-    task_inner.id = 0;
-    if task_inner.status.is_empty() || !VALID_STATUSES.contains(&task_inner.status.as_str()) {
-        task_inner.status = "todo".to_string();
-    }
-    task_inner.in_sprint = false;
-    task_inner.archived = false;
-    if let Some(msg) = validate_task(&task_inner) {
-        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
-    }
-    if !project_exists(&data.pool, &task_inner.project)
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?
-    {
-        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "project does not exist" })));
+/// Splits exceeded WIP limits into the first `reject` limit or warning messages.
+fn wip_outcome(exceeded: Vec<WipExceeded>) -> std::result::Result<Vec<String>, WipExceeded> {
+    let mut warnings = Vec::new();
+    for e in exceeded {
+        if e.limit.mode == "reject" {
+            return Err(e);
+        }
+        warnings.push(e.error);
     }
-    if !assignee_exists(&data.pool, &task_inner.assignee)
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?
-    {
-        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "assignee does not exist" })));
+    Ok(warnings)
+}
+
+#[derive(Serialize)]
+struct TaskResponse {
+    #[serde(flatten)]
+    task: Task,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+async fn record_status_change(
+    conn: &mut SqliteConnection,
+    task_id: i64,
+    from: &str,
+    to: &str,
+    comment: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    if from == to {
+        return Ok(());
     }
+    let comment = comment.map(str::trim).filter(|c| !c.is_empty());
+    sqlx::query("INSERT INTO task_status_changes (task_id, from_status, to_status, comment) VALUES (?, ?, ?, ?)")
+        .bind(task_id)
+        .bind(from)
+        .bind(to)
+        .bind(comment)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
 
-    let tags_json = serde_json::to_string(&task_inner.tags)
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+async fn next_rank<'e, E>(executor: E, status: &str) -> Result<String, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let last: String = sqlx::query_scalar("SELECT COALESCE(MAX(rank), '') FROM tasks WHERE status=?")
+        .bind(status)
+        .fetch_one(executor)
+        .await?;
+    Ok(rank_after(&last))
+}
+
+/// The rank of `current` once its status is `status`: unchanged within its column, otherwise
+/// the bottom of the new column, which is stored.
+async fn rank_for_status(conn: &mut SqliteConnection, current: &Task, status: &str) -> Result<String, sqlx::Error> {
+    if current.status == status {
+        return Ok(current.rank.clone());
+    }
+    let rank = next_rank(&mut *conn, status).await?;
+    sqlx::query("UPDATE tasks SET rank=? WHERE id=?")
+        .bind(&rank)
+        .bind(current.id)
+        .execute(conn)
+        .await?;
+    Ok(rank)
+}
 
-    let notes = task_inner.notes.as_deref().unwrap_or("").trim();
-    let notes_opt = if notes.is_empty() { None } else { Some(notes.to_string()) };
+/// Inserts a new task at the bottom of its status column and assigns it a project key.
+async fn insert_task(conn: &mut SqliteConnection, task: Task) -> Result<Task, sqlx::Error> {
+    let tags_json = serde_json::to_string(&task.tags).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+    let notes = task.notes.as_deref().map(str::trim).filter(|n| !n.is_empty()).map(String::from);
+    let rank = next_rank(&mut *conn, &task.status).await?;
+    let key = allocate_task_key(conn, &task.project).await?;
 
     let id = sqlx::query_scalar::<_, i64>(
         r#"
-        INSERT INTO tasks (title, description, tags, deadline, project, assignee, status, in_sprint, notes)
-        VALUES (?, ?, ?, ?, ?, ?, ?, 0, ?)
+        INSERT INTO tasks (title, description, tags, deadline, project, assignee, status, in_sprint, notes, rank, key, meeting_id, prompt_version, parent_id, estimate_hours)
+        VALUES (?, ?, ?, ?, ?, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?)
         RETURNING id
         "#,
     )
-    .bind(&task_inner.title)
-    .bind(&task_inner.description)
+    .bind(&task.title)
+    .bind(&task.description)
     .bind(&tags_json)
-    .bind(&task_inner.deadline)
-    .bind(&task_inner.project)
-    .bind(&task_inner.assignee)
-    .bind(&task_inner.status)
-    .bind(notes_opt.as_deref().unwrap_or(""))
-    .fetch_one(&data.pool)
-    .await
-    .map_err(actix_web::error::ErrorInternalServerError)?;
+    .bind(&task.deadline)
+    .bind(&task.project)
+    .bind(&task.assignee)
+    .bind(&task.status)
+    .bind(notes.as_deref().unwrap_or(""))
+    .bind(&rank)
+    .bind(&key)
+    .bind(task.meeting_id)
+    .bind(task.prompt_version)
+    .bind(task.parent_id)
+    .bind(task.estimate_hours)
+    .fetch_one(&mut *conn)
+    .await?;
 
-    let new_task = Task {
+    Ok(Task {
         id,
-        ..task_inner
-    };
-    Ok(HttpResponse::Created().json(new_task))
+        notes,
+        in_sprint: false,
+        archived: false,
+        rank,
+        key,
+        ..task
+    })
 }
 
-async fn update_task(
-    data: web::Data<AppState>,
-    path: web::Path<i64>,
-    task: web::Json<Task>,
-) -> Result<HttpResponse> {
-    let id = path.into_inner();
-    if let Some(msg) = validate_task(&task) {
-        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
-    }
-    if !project_exists(&data.pool, &task.project)
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?
-    {
-        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "project does not exist" })));
-    }
-    if !assignee_exists(&data.pool, &task.assignee)
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?
-    {
-        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "assignee does not exist" })));
-    }
-
-    let tags_json = serde_json::to_string(&task.tags)
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-
+/// Writes the editable fields of an existing task. Rank and key are managed separately.
+async fn save_task(conn: &mut SqliteConnection, task: &Task) -> Result<(), sqlx::Error> {
+    let tags_json = serde_json::to_string(&task.tags).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
     let notes = task.notes.as_deref().unwrap_or("").trim();
-    let notes_val = if notes.is_empty() { "" } else { notes };
-
-    let result = sqlx::query(
+    sqlx::query(
         r#"
-        UPDATE tasks SET title=?, description=?, tags=?, deadline=?, project=?, assignee=?, status=?, in_sprint=?, notes=?, archived=?
+        UPDATE tasks SET title=?, description=?, tags=?, deadline=?, project=?, assignee=?, status=?, in_sprint=?, notes=?, archived=?, estimate_hours=?
         WHERE id=?
         "#,
     )
@@ -456,447 +1744,6178 @@ async fn update_task(
     .bind(&task.assignee)
     .bind(&task.status)
     .bind(if task.in_sprint { 1 } else { 0 })
-    .bind(notes_val)
+    .bind(notes)
     .bind(if task.archived { 1 } else { 0 })
-    .bind(id)
-    .execute(&data.pool)
-    .await
-    .map_err(actix_web::error::ErrorInternalServerError)?;
-
-    if result.rows_affected() == 0 {
-        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
-    }
-
-    let updated = Task {
-        id,
-        ..task.into_inner()
-    };
-    Ok(HttpResponse::Ok().json(updated))
+    .bind(task.estimate_hours)
+    .bind(task.id)
+    .execute(conn)
+    .await?;
+    Ok(())
 }
 
-#[derive(Deserialize)]
-struct UpdateSprintRequest {
-    in_sprint: bool,
+/// Gives a task that moved to `project` a key there, keeping its old key as a redirecting alias.
+async fn rekey_task(conn: &mut SqliteConnection, task: &Task, project: &str) -> Result<Option<String>, sqlx::Error> {
+    let Some(key) = allocate_task_key(&mut *conn, project).await? else {
+        return Ok(task.key.clone());
+    };
+    if let Some(old) = &task.key {
+        sqlx::query("INSERT OR REPLACE INTO task_key_aliases (key, task_id) VALUES (?, ?)")
+            .bind(old)
+            .bind(task.id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    sqlx::query("UPDATE tasks SET key=? WHERE id=?")
+        .bind(&key)
+        .bind(task.id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(Some(key))
 }
 
-async fn update_task_sprint(
-    data: web::Data<AppState>,
-    path: web::Path<i64>,
-    body: web::Json<UpdateSprintRequest>,
-) -> Result<HttpResponse> {
-    let id = path.into_inner();
-    let result = sqlx::query("UPDATE tasks SET in_sprint=? WHERE id=?")
-        .bind(if body.in_sprint { 1 } else { 0 })
-        .bind(id)
-        .execute(&data.pool)
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-
-    if result.rows_affected() == 0 {
-        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+async fn task_response(pool: &SqlitePool, id: i64) -> Result<HttpResponse> {
+    let mut conn = pool.acquire().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    match fetch_task(&mut conn, id).await? {
+        Some(task) => Ok(HttpResponse::Ok().json(task)),
+        None => Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" }))),
     }
+}
 
-    let row = sqlx::query_as::<_, TaskRow>("SELECT id, title, description, tags, deadline, project, assignee, status, in_sprint, notes, archived FROM tasks WHERE id=?")
+async fn fetch_task(conn: &mut SqliteConnection, id: i64) -> Result<Option<Task>> {
+    let row = sqlx::query_as::<_, TaskRow>("SELECT id, title, description, tags, deadline, project, assignee, status, in_sprint, notes, archived, rank, key, meeting_id, prompt_version, parent_id, estimate_hours FROM tasks WHERE id=?")
         .bind(id)
-        .fetch_one(&data.pool)
+        .fetch_optional(conn)
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
+    row.map(|r| r.into_task())
+        .transpose()
+        .map_err(actix_web::error::ErrorInternalServerError)
+}
 
-    let task = row.into_task().map_err(actix_web::error::ErrorInternalServerError)?;
-    Ok(HttpResponse::Ok().json(task))
+/// Structured task filter shared by `GET /tasks` (as query parameters) and the bulk endpoint.
+/// `project`, `assignee`, `status` and `tag` accept comma-separated alternatives.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct TaskFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    assignee: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    in_sprint: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    archived: Option<bool>,
+    /// Case-insensitive substring of the title or description.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    search: Option<String>,
+    /// Only tasks with a deadline on or before this date (YYYY-MM-DD).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deadline_before: Option<String>,
+    /// Only tasks with a deadline on or after this date (YYYY-MM-DD).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deadline_after: Option<String>,
+    /// Only subtasks of this task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_id: Option<i64>,
 }
 
-#[derive(Deserialize)]
-struct UpdateStatusRequest {
-    status: String,
+fn filter_values(value: &Option<String>) -> Vec<&str> {
+    value
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .collect()
 }
 
-async fn update_task_status(
-    data: web::Data<AppState>,
-    path: web::Path<i64>,
-    body: web::Json<UpdateStatusRequest>,
-) -> Result<HttpResponse> {
-    let id = path.into_inner();
-    if !VALID_STATUSES.contains(&body.status.as_str()) {
-        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "invalid status" })));
+impl TaskFilter {
+    fn is_empty(&self) -> bool {
+        self.project.is_none()
+            && self.assignee.is_none()
+            && self.status.is_none()
+            && self.tag.is_none()
+            && self.in_sprint.is_none()
+            && self.archived.is_none()
+            && self.search.is_none()
+            && self.deadline_before.is_none()
+            && self.deadline_after.is_none()
+            && self.parent_id.is_none()
     }
 
-    let result = sqlx::query("UPDATE tasks SET status=? WHERE id=?")
-        .bind(&body.status)
-        .bind(id)
-        .execute(&data.pool)
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-
-    if result.rows_affected() == 0 {
-        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    /// Appends ` AND ...` conditions to a query that already has a WHERE clause.
+    fn push_conditions<'a>(&'a self, qb: &mut QueryBuilder<'a, Sqlite>) {
+        for (column, value) in [("project", &self.project), ("assignee", &self.assignee), ("status", &self.status)] {
+            let values = filter_values(value);
+            if values.is_empty() {
+                continue;
+            }
+            qb.push(format!(" AND {} IN (", column));
+            let mut list = qb.separated(", ");
+            for v in values {
+                list.push_bind(v);
+            }
+            list.push_unseparated(")");
+        }
+        let tags = filter_values(&self.tag);
+        if !tags.is_empty() {
+            qb.push(" AND EXISTS (SELECT 1 FROM json_each(tasks.tags) WHERE json_each.value IN (");
+            let mut list = qb.separated(", ");
+            for tag in tags {
+                list.push_bind(tag);
+            }
+            list.push_unseparated("))");
+        }
+        if let Some(in_sprint) = self.in_sprint {
+            qb.push(" AND in_sprint = ").push_bind(if in_sprint { 1 } else { 0 });
+        }
+        if let Some(archived) = self.archived {
+            qb.push(" AND COALESCE(archived, 0) = ").push_bind(if archived { 1 } else { 0 });
+        }
+        if let Some(search) = self.search.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            let pattern = format!("%{}%", search.to_lowercase());
+            qb.push(" AND (lower(title) LIKE ")
+                .push_bind(pattern.clone())
+                .push(" OR lower(description) LIKE ")
+                .push_bind(pattern)
+                .push(")");
+        }
+        if let Some(before) = &self.deadline_before {
+            qb.push(" AND deadline IS NOT NULL AND deadline != '' AND deadline <= ").push_bind(before);
+        }
+        if let Some(after) = &self.deadline_after {
+            qb.push(" AND deadline IS NOT NULL AND deadline != '' AND deadline >= ").push_bind(after);
+        }
+        if let Some(parent_id) = self.parent_id {
+            qb.push(" AND parent_id = ").push_bind(parent_id);
+        }
     }
+}
 
-    let row = sqlx::query_as::<_, TaskRow>("SELECT id, title, description, tags, deadline, project, assignee, status, in_sprint, notes, archived FROM tasks WHERE id=?")
-        .bind(id)
-        .fetch_one(&data.pool)
+async fn query_tasks(conn: &mut SqliteConnection, filter: &TaskFilter) -> Result<Vec<Task>> {
+    let mut qb = QueryBuilder::new(
+        "SELECT id, title, description, tags, deadline, project, assignee, status, in_sprint, notes, archived, rank, key, meeting_id, prompt_version, parent_id, estimate_hours FROM tasks WHERE 1=1",
+    );
+    filter.push_conditions(&mut qb);
+    qb.push(" ORDER BY rank, id");
+    let rows = qb
+        .build_query_as::<TaskRow>()
+        .fetch_all(conn)
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
 
-    let task = row.into_task().map_err(actix_web::error::ErrorInternalServerError)?;
-    Ok(HttpResponse::Ok().json(task))
+    rows.into_iter()
+        .map(|r| r.into_task())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(actix_web::error::ErrorInternalServerError)
 }
 
-async fn archive_done_in_sprint(data: web::Data<AppState>) -> Result<HttpResponse> {
-    let result = sqlx::query(
-        "UPDATE tasks SET archived=1, in_sprint=0 WHERE in_sprint=1 AND status='done'",
-    )
-    .execute(&data.pool)
-    .await
-    .map_err(actix_web::error::ErrorInternalServerError)?;
-
-    Ok(HttpResponse::Ok().json(serde_json::json!({ "archived": result.rows_affected() })))
+#[derive(Deserialize)]
+struct TaskListQuery {
+    #[serde(default)]
+    expand: Option<String>,
 }
 
-async fn unarchive_task(
+/// Lists tasks matching the filter. With `expand=wip` the response is an object with the
+/// `tasks` and the `wip` usage of the filtered projects, so a board can flag full columns.
+async fn get_tasks(
     data: web::Data<AppState>,
-    path: web::Path<i64>,
+    filter: web::Query<TaskFilter>,
+    list: web::Query<TaskListQuery>,
 ) -> Result<HttpResponse> {
-    let id = path.into_inner();
-    let result = sqlx::query("UPDATE tasks SET archived=0 WHERE id=?")
-        .bind(id)
-        .execute(&data.pool)
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-
-    if result.rows_affected() == 0 {
-        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    let with_wip = match list.expand.as_deref().map(str::trim) {
+        None | Some("") => false,
+        Some("wip") => true,
+        Some(other) => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("unknown expand '{}'; expected wip", other)
+            })))
+        }
+    };
+    let mut conn = data.pool.acquire().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let tasks = query_tasks(&mut conn, &filter).await?;
+    drop(conn);
+    if !with_wip {
+        return Ok(HttpResponse::Ok().json(tasks));
     }
-
-    let row = sqlx::query_as::<_, TaskRow>("SELECT id, title, description, tags, deadline, project, assignee, status, in_sprint, notes, archived FROM tasks WHERE id=?")
-        .bind(id)
-        .fetch_one(&data.pool)
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-
-    let task = row.into_task().map_err(actix_web::error::ErrorInternalServerError)?;
-    Ok(HttpResponse::Ok().json(task))
-}
-
-async fn delete_task(
-    data: web::Data<AppState>,
-    path: web::Path<i64>,
-) -> Result<HttpResponse> {
-    let id = path.into_inner();
-    let result = sqlx::query("DELETE FROM tasks WHERE id=?")
-        .bind(id)
-        .execute(&data.pool)
+    let projects = filter_values(&filter.project);
+    let wip: Vec<WipUsage> = wip_usage(&data.pool, None)
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-
-    if result.rows_affected() == 0 {
-        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
-    }
-    Ok(HttpResponse::NoContent().finish())
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .into_iter()
+        .filter(|u| projects.is_empty() || projects.contains(&u.project.as_str()))
+        .collect();
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "tasks": tasks, "wip": wip })))
 }
 
 #[derive(Deserialize)]
-struct GenerateTasksRequest {
-    meeting_notes: String,
+struct GetTaskQuery {
+    #[serde(default)]
+    expand: Option<String>,
 }
 
-#[derive(Serialize)]
-struct ChatMessage {
-    role: String,
-    content: String,
+#[derive(Serialize, Default)]
+struct TaskExpansions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project: Option<Project>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assignee: Option<Assignee>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    counts: Option<TaskCounts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meeting: Option<Meeting>,
 }
 
 #[derive(Serialize)]
-struct ChatRequest {
-    model: String,
-    messages: Vec<ChatMessage>,
-}
-
-#[derive(Deserialize)]
-struct ChatChoice {
-    message: ChatChoiceMessage,
+struct TaskCounts {
+    status_changes: i64,
+    previous_keys: i64,
+    subtasks: i64,
 }
 
-#[derive(Deserialize)]
-struct ChatChoiceMessage {
-    content: Option<String>,
-}
-
-#[derive(Deserialize)]
-struct ChatResponse {
-    choices: Option<Vec<ChatChoice>>,
-    error: Option<ChatError>,
-}
-
-#[derive(Deserialize)]
-struct ChatError {
-    message: String,
+#[derive(Serialize)]
+struct ExpandedTask {
+    #[serde(flatten)]
+    task: Task,
+    expanded: TaskExpansions,
 }
 
-fn extract_json_from_response(text: &str) -> Option<&str> {
-    let text = text.trim();
-    if let Some(start) = text.find("```json") {
-        let content_start = text[start..].find('\n').map(|i| start + i + 1).unwrap_or(start + 7);
-        if let Some(end) = text[content_start..].find("```") {
-            return Some(text[content_start..content_start + end].trim());
-        }
+async fn expand_task(
+    conn: &mut SqliteConnection,
+    task: &Task,
+    expand: &[&str],
+) -> Result<TaskExpansions, sqlx::Error> {
+    let mut expanded = TaskExpansions::default();
+    if expand.contains(&"project") {
+        expanded.project = sqlx::query_as::<_, Project>("SELECT id, name, key FROM projects WHERE name=?")
+            .bind(&task.project)
+            .fetch_optional(&mut *conn)
+            .await?;
     }
-    if let Some(start) = text.find("```") {
-        let content_start = text[start..].find('\n').map(|i| start + i + 1).unwrap_or(start + 3);
-        if let Some(end) = text[content_start..].find("```") {
-            return Some(text[content_start..content_start + end].trim());
+    if expand.contains(&"assignee") {
+        expanded.assignee = sqlx::query_as::<_, Assignee>("SELECT id, name, aliases FROM assignees WHERE name=?")
+            .bind(&task.assignee)
+            .fetch_optional(&mut *conn)
+            .await?;
+    }
+    if expand.contains(&"meeting") {
+        if let Some(meeting_id) = task.meeting_id {
+            expanded.meeting = sqlx::query_as::<_, MeetingRow>(
+                "SELECT id, title, date, attendees, notes, provider, model, language, created_at FROM meetings WHERE id=?",
+            )
+            .bind(meeting_id)
+            .fetch_optional(&mut *conn)
+            .await?
+            .map(|r| r.into_meeting())
+            .transpose()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
         }
     }
-    if text.starts_with('[') {
-        return Some(text);
+    if expand.contains(&"counts") {
+        let status_changes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM task_status_changes WHERE task_id=?")
+            .bind(task.id)
+            .fetch_one(&mut *conn)
+            .await?;
+        let previous_keys: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM task_key_aliases WHERE task_id=?")
+            .bind(task.id)
+            .fetch_one(&mut *conn)
+            .await?;
+        let subtasks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tasks WHERE parent_id=?")
+            .bind(task.id)
+            .fetch_one(&mut *conn)
+            .await?;
+        expanded.counts = Some(TaskCounts {
+            status_changes,
+            previous_keys,
+            subtasks,
+        });
     }
-    None
-}
-
-fn resolve_ai_provider() -> (AiProvider, String) {
-    let provider = std::env::var("AI_PROVIDER")
-        .unwrap_or_else(|_| "gemini".to_string())
-        .to_lowercase();
-    let provider = match provider.as_str() {
-        "xai" => AiProvider::Xai,
-        _ => AiProvider::Gemini,
-    };
-    let api_key = match provider {
-        AiProvider::Xai => std::env::var("XAI_API_KEY").unwrap_or_default(),
-        AiProvider::Gemini => std::env::var("GEMINI_API_KEY").unwrap_or_default(),
-    };
-    (provider, api_key)
+    Ok(expanded)
 }
 
-async fn generate_tasks_from_ai(
+/// Looks up a task by numeric id or by key (e.g. `BE-42`). Keys of tasks that moved to
+/// another project redirect to the task's current key.
+async fn get_task(
     data: web::Data<AppState>,
-    body: web::Json<GenerateTasksRequest>,
+    path: web::Path<String>,
+    query: web::Query<GetTaskQuery>,
 ) -> Result<HttpResponse> {
-    let (provider, api_key) = resolve_ai_provider();
-    let (api_url, model) = match provider {
-        AiProvider::Xai => (XAI_API_URL, XAI_MODEL.to_string()),
-        AiProvider::Gemini => (
-            GEMINI_API_URL,
-            std::env::var("GEMINI_MODEL").unwrap_or_else(|_| GEMINI_MODEL_DEFAULT.to_string()),
-        ),
-    };
-
-    if api_key.is_empty() {
-        let key_name = match provider {
-            AiProvider::Xai => "XAI_API_KEY",
-            AiProvider::Gemini => "GEMINI_API_KEY",
-        };
-        return Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
-            "error": format!("{} is not configured. Please set the environment variable.", key_name)
-        })));
-    }
-
-    let notes = body.meeting_notes.trim();
-    if notes.is_empty() {
+    let reference = path.into_inner();
+    let expand: Vec<&str> = query
+        .expand
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .collect();
+    if let Some(unknown) = expand.iter().find(|e| !["project", "assignee", "counts", "meeting"].contains(e)) {
         return Ok(HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "meeting_notes must not be empty"
+            "error": format!("unknown expand '{}'; expected project, assignee, counts or meeting", unknown)
         })));
     }
 
-    let system_prompt = r#"You are a task extraction assistant. Given meeting notes or any text, extract actionable tasks.
-
-Return ONLY a valid JSON array of task objects. Each task must have:
-- "title": string (required, concise task title)
-- "description": string (required, detailed description)
-- "tags": array of strings (e.g. ["meeting", "urgent"])
-- "deadline": string or null (YYYY-MM-DD format if date is mentioned, otherwise null)
-- "project": string (default "General")
-- "assignee": string (default "Unassigned" if not specified)
-- "status": string (one of "todo", "in_progress", "done", "blocked"; default "todo")
+    let mut conn = data.pool.acquire().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let id = match reference.parse::<i64>() {
+        Ok(id) => Some(id),
+        Err(_) => {
+            let key = reference.to_ascii_uppercase();
+            let id: Option<i64> = sqlx::query_scalar("SELECT id FROM tasks WHERE key=?")
+                .bind(&key)
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+            if id.is_none() {
+                let current: Option<Option<String>> = sqlx::query_scalar(
+                    "SELECT t.key FROM task_key_aliases a JOIN tasks t ON t.id = a.task_id WHERE a.key=?",
+                )
+                .bind(&key)
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+                if let Some(Some(current)) = current {
+                    let location = if expand.is_empty() {
+                        format!("/tasks/{}", current)
+                    } else {
+                        let query = serde_urlencoded::to_string([("expand", expand.join(","))])
+                            .map_err(actix_web::error::ErrorInternalServerError)?;
+                        format!("/tasks/{}?{}", current, query)
+                    };
+                    return Ok(HttpResponse::MovedPermanently()
+                        .insert_header(("Location", location))
+                        .json(serde_json::json!({ "error": "task key has moved", "key": current })));
+                }
+            }
+            id
+        }
+    };
+    let task = match id {
+        Some(id) => fetch_task(&mut conn, id).await?,
+        None => None,
+    };
+    let Some(task) = task else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    };
+    if expand.is_empty() {
+        return Ok(HttpResponse::Ok().json(task));
+    }
+    let expanded = expand_task(&mut conn, &task, &expand)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(ExpandedTask { task, expanded }))
+}
+
+#[derive(sqlx::FromRow)]
+struct TaskRow {
+    id: i64,
+    title: String,
+    description: String,
+    tags: String,
+    deadline: Option<String>,
+    project: String,
+    assignee: String,
+    status: String,
+    in_sprint: i32,
+    notes: Option<String>,
+    archived: Option<i32>,
+    rank: Option<String>,
+    key: Option<String>,
+    meeting_id: Option<i64>,
+    prompt_version: Option<i64>,
+    parent_id: Option<i64>,
+    estimate_hours: Option<f64>,
+}
+
+impl TaskRow {
+    fn into_task(self) -> Result<Task, serde_json::Error> {
+        let tags: Vec<String> = if self.tags.is_empty() {
+            Vec::new()
+        } else {
+            serde_json::from_str(&self.tags)?
+        };
+        Ok(Task {
+            id: self.id,
+            title: self.title,
+            description: self.description,
+            tags,
+            deadline: self.deadline,
+            project: self.project,
+            assignee: self.assignee,
+            status: self.status,
+            in_sprint: self.in_sprint != 0,
+            notes: self.notes.filter(|s| !s.is_empty()),
+            archived: self.archived.unwrap_or(0) != 0,
+            rank: self.rank.unwrap_or_default(),
+            key: self.key,
+            meeting_id: self.meeting_id,
+            prompt_version: self.prompt_version,
+            parent_id: self.parent_id,
+            estimate_hours: self.estimate_hours,
+        })
+    }
+}
+
+/// Lowercased words of `text` without punctuation, stopwords or a plural `s`.
+fn similarity_tokens(text: &str) -> HashSet<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty() && !SIMILARITY_STOPWORDS.contains(w))
+        .map(|w| match w.strip_suffix('s') {
+            Some(stem) if stem.len() > 2 && !stem.ends_with('s') => stem.to_string(),
+            _ => w.to_string(),
+        })
+        .collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / a.union(b).count() as f64
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| *x as f64 * *y as f64).sum();
+    let norm_a: f64 = a.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    let norm_b: f64 = b.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// The text compared (and embedded) for duplicate detection.
+fn similarity_text(task: &Task) -> String {
+    format!("{}\n{}", task.title.trim(), task.description.trim())
+}
+
+/// Word overlap of the titles, weighted above the overlap of title plus description.
+fn lexical_similarity(a: &Task, b: &Task) -> f64 {
+    let title = jaccard(&similarity_tokens(&a.title), &similarity_tokens(&b.title));
+    let full = jaccard(&similarity_tokens(&similarity_text(a)), &similarity_tokens(&similarity_text(b)));
+    0.6 * title + 0.4 * full
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SimilarTask {
+    id: i64,
+    key: Option<String>,
+    title: String,
+    status: String,
+    score: f64,
+    /// `embedding` when both tasks were compared by embeddings, otherwise `lexical`.
+    method: &'static str,
+}
+
+/// What to do with a new task that looks like a duplicate of an open task.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+enum DuplicateMode {
+    /// Create it anyway and list the likely duplicates in the response.
+    #[default]
+    Flag,
+    /// Do not create it.
+    Skip,
+    /// Fold it into the closest existing task instead of creating it.
+    Merge,
+}
+
+#[derive(Deserialize)]
+struct DuplicateQuery {
+    #[serde(default)]
+    on_duplicate: DuplicateMode,
+    #[serde(default)]
+    threshold: Option<f64>,
+}
+
+fn validate_threshold(threshold: Option<f64>) -> Option<&'static str> {
+    match threshold {
+        Some(t) if !(0.0..=1.0).contains(&t) => Some("threshold must be between 0 and 1"),
+        _ => None,
+    }
+}
+
+/// A task as returned by the create and generate endpoints, with its likely duplicates.
+#[derive(Serialize)]
+struct TaskOutcome {
+    #[serde(flatten)]
+    task: Task,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    duplicates: Vec<SimilarTask>,
+    /// `skipped` or `merged` when the task was not created because of `on_duplicate`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolution: Option<&'static str>,
+    /// Exceeded WIP limits in `warn` mode, or why a duplicate was created instead of merged.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+/// The words of `probes` that prefilter similar tasks in SQL: at least three characters, at
+/// most 200 of them, longest first since longer words are more selective.
+fn probe_words(probes: &[Task]) -> Vec<String> {
+    let mut words: Vec<String> = probes
+        .iter()
+        .flat_map(|t| similarity_tokens(&similarity_text(t)))
+        .filter(|w| w.chars().count() >= 3)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    words.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    words.truncate(200);
+    words
+}
+
+/// Open (not done, not archived) tasks in the same projects as `probes` that share a word
+/// with one of them, newest first. Only these are compared, so a check never loads every task.
+async fn duplicate_candidates(conn: &mut SqliteConnection, probes: &[Task]) -> Result<Vec<Task>> {
+    let mut projects: Vec<&str> = probes.iter().map(|t| t.project.as_str()).collect();
+    projects.sort_unstable();
+    projects.dedup();
+    let words = probe_words(probes);
+    if projects.is_empty() || words.is_empty() {
+        return Ok(Vec::new());
+    }
+    let limit = (probes.len() * DUPLICATE_CANDIDATES_PER_TASK).min(DUPLICATE_CANDIDATES_MAX) as i64;
+
+    let mut qb = QueryBuilder::new(
+        "SELECT id, title, description, tags, deadline, project, assignee, status, in_sprint, notes, archived, rank, key, meeting_id, prompt_version, parent_id, estimate_hours FROM tasks \
+         WHERE COALESCE(archived, 0)=0 AND status!='done' AND project IN (",
+    );
+    let mut list = qb.separated(", ");
+    for project in projects {
+        list.push_bind(project);
+    }
+    qb.push(") AND (");
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            qb.push(" OR ");
+        }
+        let pattern = format!("%{}%", word);
+        qb.push("title LIKE ").push_bind(pattern.clone()).push(" OR description LIKE ").push_bind(pattern);
+    }
+    qb.push(") ORDER BY id DESC LIMIT ").push_bind(limit);
+    let rows = qb
+        .build_query_as::<TaskRow>()
+        .fetch_all(conn)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    rows.into_iter()
+        .map(|r| r.into_task())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Open (not done, not archived) tasks that new tasks are compared against.
+struct DuplicateIndex {
+    tasks: Vec<Task>,
+    /// Embeddings aligned with `tasks`; `None` when the provider has no embedding model
+    /// or embedding failed, in which case comparison falls back to word overlap.
+    embeddings: Option<Vec<Vec<f32>>>,
+}
+
+impl DuplicateIndex {
+    /// Loads the candidates for `probes`, the tasks about to be checked, embedding them with
+    /// `ai` when it has an embedding model.
+    async fn load(data: &AppState, ai: &dyn AiProvider, probes: &[Task]) -> Result<Self> {
+        let mut conn = data.pool.acquire().await.map_err(actix_web::error::ErrorInternalServerError)?;
+        let tasks = duplicate_candidates(&mut conn, probes).await?;
+        let embeddings = match ai.embedding_model() {
+            Some(model) => cached_embeddings(&mut conn, ai, model, &tasks)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?,
+            None => None,
+        };
+        Ok(DuplicateIndex { tasks, embeddings })
+    }
+
+    /// Embeds the given tasks in one request when the index uses embeddings.
+    async fn embed(&mut self, ai: &dyn AiProvider, tasks: &[Task]) -> Vec<Option<Vec<f32>>> {
+        if self.embeddings.is_none() || tasks.is_empty() {
+            return vec![None; tasks.len()];
+        }
+        let inputs: Vec<String> = tasks.iter().map(similarity_text).collect();
+        match ai.embed(&inputs).await {
+            Ok(e) if e.vectors.len() == tasks.len() => e.vectors.into_iter().map(Some).collect(),
+            _ => {
+                self.embeddings = None;
+                vec![None; tasks.len()]
+            }
+        }
+    }
+
+    /// Open tasks of the same project, other than `task` itself, scoring at least `threshold`,
+    /// best match first.
+    fn matches(&self, task: &Task, embedding: Option<&[f32]>, threshold: Option<f64>) -> Vec<SimilarTask> {
+        let mut found: Vec<SimilarTask> = self
+            .tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, other)| other.id != task.id && other.project == task.project)
+            .filter_map(|(i, other)| {
+                let (score, method, default_threshold) = match (embedding, &self.embeddings) {
+                    (Some(e), Some(all)) => (cosine_similarity(e, &all[i]), "embedding", EMBEDDING_DUPLICATE_THRESHOLD),
+                    _ => (lexical_similarity(task, other), "lexical", DUPLICATE_THRESHOLD),
+                };
+                (score >= threshold.unwrap_or(default_threshold)).then(|| SimilarTask {
+                    id: other.id,
+                    key: other.key.clone(),
+                    title: other.title.clone(),
+                    status: other.status.clone(),
+                    score: (score * 1000.0).round() / 1000.0,
+                    method,
+                })
+            })
+            .collect();
+        found.sort_by(|a, b| b.score.total_cmp(&a.score));
+        found
+    }
+
+    /// The stored embedding of an indexed task.
+    fn embedding_of(&self, id: i64) -> Option<&[f32]> {
+        let i = self.tasks.iter().position(|t| t.id == id)?;
+        self.embeddings.as_ref().map(|all| all[i].as_slice())
+    }
+
+    /// Adds a task created during the current request so later tasks are compared with it.
+    fn add(&mut self, task: Task, embedding: Option<Vec<f32>>) {
+        match (&mut self.embeddings, embedding) {
+            (Some(all), Some(e)) => all.push(e),
+            (Some(_), None) => self.embeddings = None,
+            _ => {}
+        }
+        self.tasks.push(task);
+    }
+}
+
+/// Embeddings for `tasks`, reusing vectors cached in `task_embeddings` while the task text
+/// and model are unchanged. Returns `None` when the provider fails to embed.
+async fn cached_embeddings(
+    conn: &mut SqliteConnection,
+    ai: &dyn AiProvider,
+    model: &str,
+    tasks: &[Task],
+) -> Result<Option<Vec<Vec<f32>>>, sqlx::Error> {
+    let mut qb: QueryBuilder<Sqlite> =
+        QueryBuilder::new("SELECT task_id, model, content, embedding FROM task_embeddings WHERE task_id IN (");
+    let mut ids = qb.separated(", ");
+    for task in tasks {
+        ids.push_bind(task.id);
+    }
+    ids.push_unseparated(")");
+    let rows: Vec<(i64, String, String, String)> = if tasks.is_empty() {
+        Vec::new()
+    } else {
+        qb.build_query_as().fetch_all(&mut *conn).await?
+    };
+    let cached: HashMap<i64, (String, String, String)> =
+        rows.into_iter().map(|(id, m, c, e)| (id, (m, c, e))).collect();
+
+    let mut vectors: Vec<Option<Vec<f32>>> = tasks
+        .iter()
+        .map(|task| {
+            cached
+                .get(&task.id)
+                .filter(|(m, c, _)| m == model && *c == similarity_text(task))
+                .and_then(|(_, _, e)| serde_json::from_str(e).ok())
+        })
+        .collect();
+    let missing: Vec<usize> = (0..tasks.len()).filter(|&i| vectors[i].is_none()).collect();
+    if !missing.is_empty() {
+        let inputs: Vec<String> = missing.iter().map(|&i| similarity_text(&tasks[i])).collect();
+        let fresh = match ai.embed(&inputs).await {
+            Ok(e) if e.vectors.len() == inputs.len() => e.vectors,
+            _ => return Ok(None),
+        };
+        for ((i, content), vector) in missing.into_iter().zip(inputs).zip(fresh) {
+            let embedding = serde_json::to_string(&vector).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+            sqlx::query("INSERT OR REPLACE INTO task_embeddings (task_id, model, content, embedding) VALUES (?, ?, ?, ?)")
+                .bind(tasks[i].id)
+                .bind(model)
+                .bind(&content)
+                .bind(&embedding)
+                .execute(&mut *conn)
+                .await?;
+            vectors[i] = Some(vector);
+        }
+    }
+    Ok(vectors.into_iter().collect())
+}
+
+/// Folds a duplicate into the existing task: new tags are added, a missing deadline or
+/// assignee is filled in, and a description with new information is appended to the notes.
+fn merge_duplicate(existing: &mut Task, incoming: &Task) {
+    for tag in &incoming.tags {
+        if !existing.tags.contains(tag) {
+            existing.tags.push(tag.clone());
+        }
+    }
+    if existing.deadline.is_none() {
+        existing.deadline = incoming.deadline.clone();
+    }
+    if existing.assignee == "Unassigned" {
+        existing.assignee = incoming.assignee.clone();
+    }
+    let description = incoming.description.trim();
+    let notes = existing.notes.clone().unwrap_or_default();
+    let known = similarity_tokens(&format!("{}\n{}", similarity_text(existing), notes));
+    if !similarity_tokens(description).is_subset(&known) {
+        existing.notes = Some(if notes.is_empty() {
+            description.to_string()
+        } else {
+            format!("{}\n\n{}", notes, description)
+        });
+    }
+}
+
+/// Creates a validated new task unless it duplicates an open task and `mode` says to skip or
+/// merge it. A merge goes through the same checks as an update; when they refuse it, the task
+/// is created and flagged instead. Created tasks are added to `index`.
+async fn insert_checked_task(
+    conn: &mut SqliteConnection,
+    index: &mut DuplicateIndex,
+    task: Task,
+    embedding: Option<Vec<f32>>,
+    mode: DuplicateMode,
+    threshold: Option<f64>,
+) -> Result<std::result::Result<TaskOutcome, WipExceeded>> {
+    let duplicates = index.matches(&task, embedding.as_deref(), threshold);
+    match (mode, duplicates.first()) {
+        (DuplicateMode::Skip, Some(_)) => {
+            return Ok(Ok(TaskOutcome {
+                task,
+                duplicates,
+                resolution: Some("skipped"),
+                warnings: Vec::new(),
+            }))
+        }
+        (DuplicateMode::Merge, Some(best)) => {
+            let Some(current) = fetch_task(&mut *conn, best.id).await? else {
+                return Err(actix_web::error::ErrorInternalServerError("duplicate task disappeared"));
+            };
+            let mut merged = current.clone();
+            merge_duplicate(&mut merged, &task);
+            match check_merge(conn, &current, &merged).await? {
+                Ok(warnings) => {
+                    save_task(&mut *conn, &merged)
+                        .await
+                        .map_err(actix_web::error::ErrorInternalServerError)?;
+                    return Ok(Ok(TaskOutcome {
+                        task: merged,
+                        duplicates,
+                        resolution: Some("merged"),
+                        warnings,
+                    }));
+                }
+                Err(reason) => {
+                    let (created, wip_warnings) = match insert_within_wip(&mut *conn, task).await? {
+                        Ok(created) => created,
+                        Err(reject) => return Ok(Err(reject)),
+                    };
+                    index.add(created.clone(), embedding);
+                    let mut warnings = vec![format!("not merged into task {}: {}", current.id, reason)];
+                    warnings.extend(wip_warnings);
+                    return Ok(Ok(TaskOutcome {
+                        task: created,
+                        duplicates,
+                        resolution: None,
+                        warnings,
+                    }));
+                }
+            }
+        }
+        _ => {}
+    }
+    let (created, warnings) = match insert_within_wip(&mut *conn, task).await? {
+        Ok(created) => created,
+        Err(reject) => return Ok(Err(reject)),
+    };
+    index.add(created.clone(), embedding);
+    Ok(Ok(TaskOutcome {
+        task: created,
+        duplicates,
+        resolution: None,
+        warnings,
+    }))
+}
+
+/// Inserts a new task unless its column is at a `reject` WIP limit. Returns the task with the
+/// warnings of exceeded `warn` limits, or the limit that refused it.
+async fn insert_within_wip(
+    conn: &mut SqliteConnection,
+    task: Task,
+) -> Result<std::result::Result<(Task, Vec<String>), WipExceeded>> {
+    let exceeded = check_wip(&mut *conn, None, &task)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let warnings = match wip_outcome(exceeded) {
+        Ok(warnings) => warnings,
+        Err(reject) => return Ok(Err(reject)),
+    };
+    let created = insert_task(conn, task)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(Ok((created, warnings)))
+}
+
+/// Runs the checks `PUT /tasks/{id}` applies on a merged duplicate: validation, transition
+/// rules and WIP limits. Returns the WIP warnings, or why the merge is refused.
+async fn check_merge(
+    conn: &mut SqliteConnection,
+    current: &Task,
+    merged: &Task,
+) -> Result<std::result::Result<Vec<String>, String>> {
+    if let Some(msg) = validate_task(merged) {
+        return Ok(Err(msg.to_string()));
+    }
+    if let Some(violation) = check_transition(conn, &current.status, merged, None)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        return Ok(Err(violation.error));
+    }
+    let exceeded = check_wip(conn, Some(current), merged)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(wip_outcome(exceeded).map_err(|reject| reject.error))
+}
+
+/// Creates a batch of validated new tasks in one transaction, checking each against open
+/// tasks and the tasks created before it. A task refused by a WIP limit is returned with the
+/// limit instead of being created.
+async fn insert_checked_tasks(
+    data: &AppState,
+    ai: &dyn AiProvider,
+    tasks: Vec<Task>,
+    mode: DuplicateMode,
+    threshold: Option<f64>,
+) -> Result<Vec<std::result::Result<TaskOutcome, (Task, WipExceeded)>>> {
+    let mut index = DuplicateIndex::load(data, ai, &tasks).await?;
+    let embeddings = index.embed(ai, &tasks).await;
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let mut outcomes = Vec::with_capacity(tasks.len());
+    for (task, embedding) in tasks.into_iter().zip(embeddings) {
+        let outcome = insert_checked_task(&mut tx, &mut index, task.clone(), embedding, mode, threshold).await?;
+        outcomes.push(outcome.map_err(|reject| (task, reject)));
+    }
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(outcomes)
+}
+
+/// Flags likely duplicates of proposed tasks without creating anything.
+async fn flag_duplicates(data: &AppState, ai: &dyn AiProvider, tasks: Vec<Task>) -> Result<Vec<TaskOutcome>> {
+    let mut index = DuplicateIndex::load(data, ai, &tasks).await?;
+    let embeddings = index.embed(ai, &tasks).await;
+    Ok(tasks
+        .into_iter()
+        .zip(embeddings)
+        .map(|(task, embedding)| TaskOutcome {
+            duplicates: index.matches(&task, embedding.as_deref(), None),
+            task,
+            resolution: None,
+            warnings: Vec::new(),
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct SimilarQuery {
+    #[serde(default)]
+    threshold: Option<f64>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+/// Open tasks that look like duplicates of the given task.
+async fn get_similar_tasks(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    query: web::Query<SimilarQuery>,
+) -> Result<HttpResponse> {
+    if let Some(msg) = validate_threshold(query.threshold) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+    let mut conn = data.pool.acquire().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(task) = fetch_task(&mut conn, path.into_inner()).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    };
+    drop(conn);
+
+    let ai = duplicate_check_provider(&data, request_user(&req), None);
+    let mut index = DuplicateIndex::load(&data, &ai, std::slice::from_ref(&task)).await?;
+    let embedding = match index.embedding_of(task.id) {
+        Some(e) => Some(e.to_vec()),
+        None => index.embed(&ai, std::slice::from_ref(&task)).await.pop().flatten(),
+    };
+    let mut similar = index.matches(&task, embedding.as_deref(), query.threshold);
+    similar.truncate(query.limit.unwrap_or(10));
+    Ok(HttpResponse::Ok().json(similar))
+}
+
+/// A suggested assignee or tag.
+#[derive(Serialize)]
+struct Suggestion {
+    value: String,
+    /// From 0 to 1.
+    confidence: f64,
+}
+
+/// A past task a suggestion is based on.
+#[derive(Serialize)]
+struct HistoryMatch {
+    id: i64,
+    key: Option<String>,
+    title: String,
+    assignee: String,
+    status: String,
+    tags: Vec<String>,
+    score: f64,
+}
+
+#[derive(Serialize)]
+struct TaskSuggestions {
+    assignee: Option<Suggestion>,
+    tags: Vec<Suggestion>,
+    /// The most similar past tasks.
+    similar: Vec<HistoryMatch>,
+    /// `history`, or `ai` when the provider made the suggestion.
+    source: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Past tasks, archived ones included, that assignees and tags are suggested from.
+struct TaskHistory {
+    tasks: Vec<Task>,
+    /// Assignees that still exist; people who have left are never suggested.
+    assignees: HashSet<String>,
+}
+
+impl TaskHistory {
+    /// Loads the past tasks that share a word or a tag with one of `probes`, the tasks that
+    /// suggestions will be made for; the newest `SUGGESTION_HISTORY_MAX` of them.
+    async fn load(pool: &SqlitePool, probes: &[Task]) -> Result<Self> {
+        let mut conn = pool.acquire().await.map_err(actix_web::error::ErrorInternalServerError)?;
+        let words = probe_words(probes);
+        let tags: HashSet<String> = probes.iter().flat_map(|t| t.tags.iter().map(|tag| tag.to_lowercase())).collect();
+        let mut tasks = Vec::new();
+        if !words.is_empty() || !tags.is_empty() {
+            let mut qb = QueryBuilder::new(
+                "SELECT id, title, description, tags, deadline, project, assignee, status, in_sprint, notes, archived, rank, key, meeting_id, prompt_version, parent_id, estimate_hours FROM tasks WHERE ",
+            );
+            let mut conditions = qb.separated(" OR ");
+            for word in &words {
+                let pattern = format!("%{}%", word);
+                conditions
+                    .push("title LIKE ")
+                    .push_bind_unseparated(pattern.clone())
+                    .push_unseparated(" OR description LIKE ")
+                    .push_bind_unseparated(pattern);
+            }
+            for tag in &tags {
+                // Tags are stored as a JSON array; match the quoted string.
+                let quoted = serde_json::to_string(tag).map_err(actix_web::error::ErrorInternalServerError)?;
+                conditions.push("tags LIKE ").push_bind_unseparated(format!("%{}%", quoted));
+            }
+            qb.push(" ORDER BY id DESC LIMIT ").push_bind(SUGGESTION_HISTORY_MAX);
+            tasks = qb
+                .build_query_as::<TaskRow>()
+                .fetch_all(&mut *conn)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?
+                .into_iter()
+                .map(|r| r.into_task())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+        }
+        let assignees = sqlx::query_scalar::<_, String>("SELECT name FROM assignees WHERE name != 'Unassigned'")
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?
+            .into_iter()
+            .collect();
+        Ok(TaskHistory { tasks, assignees })
+    }
+
+    /// Past tasks similar to `task`, best first. Title and description overlap is combined
+    /// with tag overlap when `task` has tags; tasks in other projects count half.
+    fn matches(&self, task: &Task) -> Vec<(&Task, f64)> {
+        let lower = |tags: &[String]| tags.iter().map(|t| t.to_lowercase()).collect::<HashSet<_>>();
+        let task_tags = lower(&task.tags);
+        let mut matches: Vec<(&Task, f64)> = self
+            .tasks
+            .iter()
+            .filter(|past| past.id != task.id)
+            .filter_map(|past| {
+                let mut score = lexical_similarity(task, past);
+                if !task_tags.is_empty() {
+                    score = 0.7 * score + 0.3 * jaccard(&task_tags, &lower(&past.tags));
+                }
+                if past.project != task.project {
+                    score *= 0.5;
+                }
+                (score >= SUGGESTION_MIN_SIMILARITY).then_some((past, score))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.1.total_cmp(&a.1));
+        matches
+    }
+
+    /// Suggests whoever completed the most similar tasks, and the tags common on similar
+    /// tasks that `task` does not have yet.
+    fn suggest(&self, task: &Task) -> TaskSuggestions {
+        let matches = self.matches(task);
+
+        let mut votes: HashMap<&str, f64> = HashMap::new();
+        let mut done_weight = 0.0;
+        for (past, score) in &matches {
+            if past.status == "done" && self.assignees.contains(&past.assignee) {
+                *votes.entry(past.assignee.as_str()).or_default() += score;
+                done_weight += score;
+            }
+        }
+        let assignee = votes
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(a.0)))
+            .map(|(name, weight)| Suggestion {
+                value: name.to_string(),
+                confidence: round2(weight / (done_weight + SUGGESTION_PRIOR)),
+            });
+
+        let existing: HashSet<String> = task.tags.iter().map(|t| t.to_lowercase()).collect();
+        let total_weight: f64 = matches.iter().map(|(_, score)| score).sum();
+        let mut tag_votes: HashMap<String, f64> = HashMap::new();
+        for (past, score) in &matches {
+            let tags: HashSet<String> = past.tags.iter().map(|t| t.to_lowercase()).collect();
+            for tag in tags.into_iter().filter(|t| !existing.contains(t)) {
+                *tag_votes.entry(tag).or_default() += score;
+            }
+        }
+        let mut tags: Vec<Suggestion> = tag_votes
+            .into_iter()
+            .map(|(tag, weight)| Suggestion {
+                value: tag,
+                confidence: round2(weight / (total_weight + SUGGESTION_PRIOR)),
+            })
+            .filter(|s| s.confidence >= 0.25)
+            .collect();
+        tags.sort_by(|a, b| b.confidence.total_cmp(&a.confidence).then(a.value.cmp(&b.value)));
+        tags.truncate(5);
+
+        TaskSuggestions {
+            assignee,
+            tags,
+            similar: matches
+                .iter()
+                .take(5)
+                .map(|(past, score)| HistoryMatch {
+                    id: past.id,
+                    key: past.key.clone(),
+                    title: past.title.clone(),
+                    assignee: past.assignee.clone(),
+                    status: past.status.clone(),
+                    tags: past.tags.clone(),
+                    score: round2(*score),
+                })
+                .collect(),
+            source: "history",
+            reason: None,
+        }
+    }
+}
+
+/// Gives extracted tasks left unassigned the assignee suggested by their history when the
+/// suggestion is confident enough, and reports it under `names`.
+async fn suggest_assignees(data: &AppState, proposed: &mut ProposedTasks) -> Result<()> {
+    if !proposed.tasks.iter().any(|t| t.assignee == "Unassigned") {
+        return Ok(());
+    }
+    let unassigned: Vec<Task> = proposed.tasks.iter().filter(|t| t.assignee == "Unassigned").cloned().collect();
+    let history = TaskHistory::load(&data.pool, &unassigned).await?;
+    for task in proposed.tasks.iter_mut().filter(|t| t.assignee == "Unassigned") {
+        let Some(suggestion) = history.suggest(task).assignee else {
+            continue;
+        };
+        if suggestion.confidence < data.extraction.assign_min_confidence {
+            continue;
+        }
+        task.assignee = suggestion.value.clone();
+        record_name_resolution(
+            &mut proposed.names,
+            NameResolution {
+                field: "assignee".to_string(),
+                returned: "Unassigned".to_string(),
+                used: suggestion.value,
+                status: NameStatus::Suggested,
+                method: None,
+                candidates: Vec::new(),
+                confidence: Some(suggestion.confidence),
+                tasks: vec![task.title.clone()],
+            },
+        );
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct SuggestionQuery {
+    /// Ask the AI provider, giving it the similar past tasks, instead of only counting them.
+    #[serde(default)]
+    ai: bool,
+}
+
+#[derive(Deserialize)]
+struct AiSuggestion {
+    assignee: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    confidence: Option<f64>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+fn task_suggestion_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "assignee": { "type": ["string", "null"] },
+            "tags": { "type": "array", "items": { "type": "string" } },
+            "confidence": { "type": "number" },
+            "reason": { "type": "string" }
+        },
+        "required": ["assignee", "tags", "confidence", "reason"],
+        "additionalProperties": false
+    })
+}
+
+/// Suggests an assignee and tags for a task from similar past tasks: who completed them and
+/// how they were tagged. With `?ai=true` the provider makes the suggestion from the same
+/// history.
+async fn get_task_suggestions(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    query: web::Query<SuggestionQuery>,
+) -> Result<HttpResponse> {
+    let mut conn = data.pool.acquire().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(task) = fetch_task(&mut conn, path.into_inner()).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    };
+    drop(conn);
+
+    let history = TaskHistory::load(&data.pool, std::slice::from_ref(&task)).await?;
+    let mut suggestions = history.suggest(&task);
+    if !query.ai {
+        return Ok(HttpResponse::Ok().json(suggestions));
+    }
+    if let Some(resp) = ai_unavailable(&data) {
+        return Ok(resp);
+    }
+    if let Some(resp) = ai_over_budget(&data).await? {
+        return Ok(resp);
+    }
+
+    let template = latest_prompt(&data.pool, SUGGESTION_PROMPT)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("task suggestion prompt is missing"))?;
+    let (_, assignees) = load_known_names(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let system = render_prompt(
+        &template.template,
+        &[
+            ("today", data.deadlines.today().format("%Y-%m-%d").to_string()),
+            ("assignees", describe_names(&assignees)),
+            ("conventions", template.conventions),
+        ],
+    );
+    let mut facts = format!(
+        "Task: {}\nProject: {}\nTags: {}\nDescription: {}\n\nSimilar past tasks:\n",
+        task.title,
+        task.project,
+        task.tags.join(", "),
+        clip(&task.description, 500)
+    );
+    let matches = history.matches(&task);
+    if matches.is_empty() {
+        facts.push_str("(none)\n");
+    }
+    for (past, score) in matches.iter().take(15) {
+        facts.push_str(&format!(
+            "- {} (project: {}, assignee: {}, status: {}, tags: {}, similarity: {:.2})\n",
+            past.title,
+            past.project,
+            past.assignee,
+            past.status,
+            past.tags.join(", "),
+            score
+        ));
+    }
+    let ai = MeteredProvider {
+        inner: data.ai.clone(),
+        data: data.clone(),
+        operation: "task_suggestion",
+        user: request_user(&req),
+        job_id: None,
+    };
+    let messages = vec![ChatMessage::system(system), ChatMessage::user(facts)];
+    let parse = |content: &str| {
+        let json_str = extract_json_from_response(content).unwrap_or(content.trim());
+        serde_json::from_str::<AiSuggestion>(json_str).map_err(|e| e.to_string())
+    };
+    let answer = match complete_parsed(&ai, messages, "task_suggestion", &task_suggestion_schema(), "a suggestion", parse).await {
+        Ok((answer, _)) => answer,
+        Err(e) => return Ok(e.into_response()),
+    };
+
+    let confidence = round2(answer.confidence.unwrap_or(0.5).clamp(0.0, 1.0));
+    suggestions.assignee = answer
+        .assignee
+        .and_then(|name| match resolve_name(&assignees, &name) {
+            ResolvedName::Exact => Some(name),
+            ResolvedName::Matched(name, _) => Some(name),
+            _ => None,
+        })
+        .filter(|name| name != "Unassigned")
+        .map(|value| Suggestion { value, confidence });
+    let existing: HashSet<String> = task.tags.iter().map(|t| t.to_lowercase()).collect();
+    let mut tags: Vec<String> = Vec::new();
+    for tag in answer.tags.iter().map(|t| t.trim().to_lowercase()) {
+        if !tag.is_empty() && !existing.contains(&tag) && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    suggestions.tags = tags.into_iter().map(|value| Suggestion { value, confidence }).collect();
+    suggestions.source = "ai";
+    suggestions.reason = answer.reason.filter(|r| !r.trim().is_empty());
+    Ok(HttpResponse::Ok().json(suggestions))
+}
+
+/// Normalizes a task about to be created and checks it, returning the validation error if any.
+async fn prepare_new_task(data: &AppState, task: &mut Task) -> Result<Option<&'static str>, sqlx::Error> {
+    let pool = &data.pool;
+    task.id = 0;
+    if task.status.is_empty() || !VALID_STATUSES.contains(&task.status.as_str()) {
+        task.status = "todo".to_string();
+    }
+    task.in_sprint = false;
+    task.archived = false;
+    resolve_task_deadline(task, &data.deadlines);
+    if let Some(msg) = validate_task(task) {
+        return Ok(Some(msg));
+    }
+    if !project_exists(pool, &task.project).await? {
+        return Ok(Some("project does not exist"));
+    }
+    if !assignee_exists(pool, &task.assignee).await? {
+        return Ok(Some("assignee does not exist"));
+    }
+    if let Some(meeting_id) = task.meeting_id {
+        let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM meetings WHERE id = ?")
+            .bind(meeting_id)
+            .fetch_optional(pool)
+            .await?;
+        if exists.is_none() {
+            return Ok(Some("meeting does not exist"));
+        }
+    }
+    if let Some(parent_id) = task.parent_id {
+        let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM tasks WHERE id = ?")
+            .bind(parent_id)
+            .fetch_optional(pool)
+            .await?;
+        if exists.is_none() {
+            return Ok(Some("parent task does not exist"));
+        }
+    }
+    Ok(None)
+}
+
+async fn create_task(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<DuplicateQuery>,
+    task: web::Json<Task>,
+) -> Result<HttpResponse> {
+    let mut task_inner = task.into_inner();
+    if let Some(msg) = validate_threshold(query.threshold) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+    if let Some(msg) = prepare_new_task(&data, &mut task_inner)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+
+    let ai = duplicate_check_provider(&data, request_user(&req), None);
+    let outcome = match insert_checked_tasks(&data, &ai, vec![task_inner], query.on_duplicate, query.threshold)
+        .await?
+        .pop()
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("task was not created"))?
+    {
+        Ok(outcome) => outcome,
+        Err((_, reject)) => return Ok(HttpResponse::UnprocessableEntity().json(reject)),
+    };
+    match outcome.resolution {
+        Some("skipped") => Ok(HttpResponse::Conflict().json(serde_json::json!({
+            "error": "task looks like a duplicate of an open task",
+            "duplicates": outcome.duplicates
+        }))),
+        Some(_) => Ok(HttpResponse::Ok().json(outcome)),
+        None => Ok(HttpResponse::Created().json(outcome)),
+    }
+}
+
+#[derive(Deserialize)]
+struct UpdateTaskRequest {
+    #[serde(flatten)]
+    task: Task,
+    #[serde(default)]
+    comment: Option<String>,
+    /// `None` when the field is absent, which keeps the stored estimate; `null` clears it.
+    #[serde(default, deserialize_with = "present")]
+    estimate_hours: Option<Option<f64>>,
+}
+
+/// Deserializes a field that is present, even as `null`, into `Some`.
+fn present<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+async fn update_task(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: web::Json<UpdateTaskRequest>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let UpdateTaskRequest {
+        mut task,
+        comment,
+        estimate_hours,
+    } = body.into_inner();
+    task.id = id;
+    task.estimate_hours = estimate_hours.flatten();
+    resolve_task_deadline(&mut task, &data.deadlines);
+    if let Some(msg) = validate_task(&task) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+    if comment.as_ref().is_some_and(|c| c.len() > 2000) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "comment must be at most 2000 characters" })));
+    }
+    if !project_exists(&data.pool, &task.project)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "project does not exist" })));
+    }
+    if !assignee_exists(&data.pool, &task.assignee)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "assignee does not exist" })));
+    }
+
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(current) = fetch_task(&mut tx, id).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    };
+    if estimate_hours.is_none() {
+        task.estimate_hours = current.estimate_hours;
+    }
+    if let Some(violation) = check_transition(&mut tx, &current.status, &task, comment.as_deref())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        return Ok(HttpResponse::UnprocessableEntity().json(violation));
+    }
+    let exceeded = check_wip(&mut tx, Some(&current), &task)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let warnings = match wip_outcome(exceeded) {
+        Ok(warnings) => warnings,
+        Err(reject) => return Ok(HttpResponse::UnprocessableEntity().json(reject)),
+    };
+
+    save_task(&mut tx, &task)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let rank = rank_for_status(&mut tx, &current, &task.status)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    record_status_change(&mut tx, id, &current.status, &task.status, comment.as_deref())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let key = if current.project != task.project {
+        rekey_task(&mut tx, &current, &task.project)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?
+    } else {
+        current.key
+    };
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    // Fields `save_task` does not write keep their stored values.
+    let updated = Task {
+        rank,
+        key,
+        meeting_id: current.meeting_id,
+        prompt_version: current.prompt_version,
+        parent_id: current.parent_id,
+        ..task
+    };
+    Ok(HttpResponse::Ok().json(TaskResponse { task: updated, warnings }))
+}
+
+#[derive(Deserialize)]
+struct UpdateSprintRequest {
+    in_sprint: bool,
+}
+
+async fn update_task_sprint(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: web::Json<UpdateSprintRequest>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let result = sqlx::query("UPDATE tasks SET in_sprint=? WHERE id=?")
+        .bind(if body.in_sprint { 1 } else { 0 })
+        .bind(id)
+        .execute(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    }
+
+    task_response(&data.pool, id).await
+}
+
+#[derive(Deserialize)]
+struct UpdateStatusRequest {
+    status: String,
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+async fn update_task_status(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: web::Json<UpdateStatusRequest>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    if !VALID_STATUSES.contains(&body.status.as_str()) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "invalid status" })));
+    }
+    if body.comment.as_ref().is_some_and(|c| c.len() > 2000) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "comment must be at most 2000 characters" })));
+    }
+
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(current) = fetch_task(&mut tx, id).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    };
+    let mut task = Task {
+        status: body.status.clone(),
+        ..current.clone()
+    };
+    if let Some(violation) = check_transition(&mut tx, &current.status, &task, body.comment.as_deref())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        return Ok(HttpResponse::UnprocessableEntity().json(violation));
+    }
+    let exceeded = check_wip(&mut tx, Some(&current), &task)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let warnings = match wip_outcome(exceeded) {
+        Ok(warnings) => warnings,
+        Err(reject) => return Ok(HttpResponse::UnprocessableEntity().json(reject)),
+    };
+
+    sqlx::query("UPDATE tasks SET status=? WHERE id=?")
+        .bind(&task.status)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    task.rank = rank_for_status(&mut tx, &current, &task.status)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    record_status_change(&mut tx, id, &current.status, &task.status, body.comment.as_deref())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(TaskResponse { task, warnings }))
+}
+
+#[derive(Deserialize)]
+struct MoveTaskRequest {
+    status: String,
+    #[serde(default)]
+    before_id: Option<i64>,
+    #[serde(default)]
+    after_id: Option<i64>,
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+/// Rank of a neighbour task, provided it is in `status` and is not the task being moved.
+async fn neighbour_rank(
+    conn: &mut SqliteConnection,
+    neighbour_id: i64,
+    task_id: i64,
+    status: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    if neighbour_id == task_id {
+        return Ok(None);
+    }
+    sqlx::query_scalar("SELECT COALESCE(rank, '') FROM tasks WHERE id=? AND status=?")
+        .bind(neighbour_id)
+        .bind(status)
+        .fetch_optional(conn)
+        .await
+}
+
+async fn move_task(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: web::Json<MoveTaskRequest>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    if !VALID_STATUSES.contains(&body.status.as_str()) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "invalid status" })));
+    }
+    if body.comment.as_ref().is_some_and(|c| c.len() > 2000) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "comment must be at most 2000 characters" })));
+    }
+
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(current) = fetch_task(&mut tx, id).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    };
+
+    let mut after_rank = None;
+    if let Some(after_id) = body.after_id {
+        after_rank = neighbour_rank(&mut tx, after_id, id, &body.status)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+        if after_rank.is_none() {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "after_id must be another task in the target status" })));
+        }
+    }
+    let mut before_rank = None;
+    if let Some(before_id) = body.before_id {
+        before_rank = neighbour_rank(&mut tx, before_id, id, &body.status)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+        if before_rank.is_none() {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "before_id must be another task in the target status" })));
+        }
+    }
+
+    // With a single neighbour given, the other bound is the adjacent task in the column.
+    let (lo, hi) = match (after_rank, before_rank) {
+        (Some(lo), Some(hi)) => (lo, Some(hi)),
+        (Some(lo), None) => {
+            let hi: Option<String> = sqlx::query_scalar(
+                "SELECT rank FROM tasks WHERE status=? AND id!=? AND rank > ? ORDER BY rank LIMIT 1",
+            )
+            .bind(&body.status)
+            .bind(id)
+            .bind(&lo)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+            (lo, hi)
+        }
+        (None, Some(hi)) => {
+            let lo: String = sqlx::query_scalar(
+                "SELECT COALESCE(MAX(rank), '') FROM tasks WHERE status=? AND id!=? AND rank < ?",
+            )
+            .bind(&body.status)
+            .bind(id)
+            .bind(&hi)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+            (lo, Some(hi))
+        }
+        (None, None) => {
+            let lo: String = sqlx::query_scalar("SELECT COALESCE(MAX(rank), '') FROM tasks WHERE status=? AND id!=?")
+                .bind(&body.status)
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+            (lo, None)
+        }
+    };
+    if hi.as_deref().is_some_and(|hi| hi <= lo.as_str()) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "after_id must be ranked before before_id" })));
+    }
+
+    let task = Task {
+        status: body.status.clone(),
+        rank: rank_between(&lo, hi.as_deref()),
+        ..current.clone()
+    };
+    if let Some(violation) = check_transition(&mut tx, &current.status, &task, body.comment.as_deref())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        return Ok(HttpResponse::UnprocessableEntity().json(violation));
+    }
+    let exceeded = check_wip(&mut tx, Some(&current), &task)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let warnings = match wip_outcome(exceeded) {
+        Ok(warnings) => warnings,
+        Err(reject) => return Ok(HttpResponse::UnprocessableEntity().json(reject)),
+    };
+
+    sqlx::query("UPDATE tasks SET status=?, rank=? WHERE id=?")
+        .bind(&task.status)
+        .bind(&task.rank)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    record_status_change(&mut tx, id, &current.status, &task.status, body.comment.as_deref())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(TaskResponse { task, warnings }))
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BulkOperation {
+    SetStatus {
+        status: String,
+        #[serde(default)]
+        comment: Option<String>,
+    },
+    SetAssignee {
+        assignee: String,
+    },
+    SetProject {
+        project: String,
+    },
+    AddTags {
+        tags: Vec<String>,
+    },
+    RemoveTags {
+        tags: Vec<String>,
+    },
+    SetSprint {
+        in_sprint: bool,
+    },
+    Archive,
+    Delete,
+}
+
+#[derive(Deserialize)]
+struct BulkRequest {
+    #[serde(default)]
+    ids: Option<Vec<i64>>,
+    #[serde(default)]
+    filter: Option<TaskFilter>,
+    operation: BulkOperation,
+}
+
+#[derive(Serialize)]
+struct BulkResult {
+    id: i64,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task: Option<Task>,
+    /// Error details (`error` message plus the failed rule or limit), flattened into the result.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    error: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+impl BulkResult {
+    fn failed(id: i64, error: serde_json::Value) -> Self {
+        BulkResult {
+            id,
+            ok: false,
+            task: None,
+            error: Some(error),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Applies one bulk operation to a single task inside the bulk transaction.
+async fn apply_bulk_operation(
+    conn: &mut SqliteConnection,
+    id: i64,
+    operation: &BulkOperation,
+) -> Result<BulkResult> {
+    let Some(current) = fetch_task(conn, id).await? else {
+        return Ok(BulkResult::failed(id, serde_json::json!({ "error": "task not found" })));
+    };
+    let mut task = current.clone();
+    let mut comment = None;
+    match operation {
+        BulkOperation::SetStatus { status, comment: c } => {
+            task.status = status.clone();
+            comment = c.as_deref();
+        }
+        BulkOperation::SetAssignee { assignee } => task.assignee = assignee.clone(),
+        BulkOperation::SetProject { project } => task.project = project.clone(),
+        BulkOperation::AddTags { tags } => {
+            for tag in tags {
+                if !task.tags.contains(tag) {
+                    task.tags.push(tag.clone());
+                }
+            }
+        }
+        BulkOperation::RemoveTags { tags } => task.tags.retain(|t| !tags.contains(t)),
+        BulkOperation::SetSprint { in_sprint } => task.in_sprint = *in_sprint,
+        BulkOperation::Archive => {
+            task.archived = true;
+            task.in_sprint = false;
+        }
+        BulkOperation::Delete => {
+            delete_task_rows(conn, id)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+            return Ok(BulkResult {
+                id,
+                ok: true,
+                task: None,
+                error: None,
+                warnings: Vec::new(),
+            });
+        }
+    }
+    if let Some(msg) = validate_task(&task) {
+        return Ok(BulkResult::failed(id, serde_json::json!({ "error": msg })));
+    }
+    if let Some(violation) = check_transition(conn, &current.status, &task, comment)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        return Ok(BulkResult::failed(id, serde_json::json!(violation)));
+    }
+    let exceeded = check_wip(conn, Some(&current), &task)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let warnings = match wip_outcome(exceeded) {
+        Ok(warnings) => warnings,
+        Err(reject) => return Ok(BulkResult::failed(id, serde_json::json!(reject))),
+    };
+
+    save_task(conn, &task)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    task.rank = rank_for_status(conn, &current, &task.status)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    record_status_change(conn, id, &current.status, &task.status, comment)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if current.project != task.project {
+        task.key = rekey_task(conn, &current, &task.project)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+    Ok(BulkResult {
+        id,
+        ok: true,
+        task: Some(task),
+        error: None,
+        warnings,
+    })
+}
+
+/// Runs one operation over a list of ids or a filter in a single transaction.
+/// If any task fails, nothing is changed and the per-task results explain why.
+async fn bulk_tasks(
+    data: web::Data<AppState>,
+    body: web::Json<BulkRequest>,
+) -> Result<HttpResponse> {
+    let BulkRequest { ids, filter, operation } = body.into_inner();
+    if ids.is_some() == filter.is_some() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "exactly one of ids or filter is required" })));
+    }
+    if filter.as_ref().is_some_and(|f| f.is_empty()) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "filter must have at least one condition" })));
+    }
+    let invalid = match &operation {
+        BulkOperation::SetStatus { status, .. } if !VALID_STATUSES.contains(&status.as_str()) => Some("invalid status"),
+        BulkOperation::SetStatus { comment: Some(c), .. } if c.len() > 2000 => {
+            Some("comment must be at most 2000 characters")
+        }
+        BulkOperation::SetAssignee { assignee } => (!assignee_exists(&data.pool, assignee)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?)
+        .then_some("assignee does not exist"),
+        BulkOperation::SetProject { project } => (!project_exists(&data.pool, project)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?)
+        .then_some("project does not exist"),
+        _ => None,
+    };
+    if let Some(msg) = invalid {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let ids = match (ids, filter) {
+        (Some(ids), _) => ids,
+        (None, Some(filter)) => query_tasks(&mut tx, &filter).await?.into_iter().map(|t| t.id).collect(),
+        (None, None) => Vec::new(),
+    };
+
+    let mut results = Vec::with_capacity(ids.len());
+    for id in ids {
+        results.push(apply_bulk_operation(&mut tx, id, &operation).await?);
+    }
+
+    if results.iter().any(|r| !r.ok) {
+        tx.rollback().await.map_err(actix_web::error::ErrorInternalServerError)?;
+        return Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({
+            "error": "bulk operation failed; no tasks were changed",
+            "results": results,
+        })));
+    }
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "results": results })))
+}
+
+async fn archive_done_in_sprint(data: web::Data<AppState>) -> Result<HttpResponse> {
+    let result = sqlx::query(
+        "UPDATE tasks SET archived=1, in_sprint=0 WHERE in_sprint=1 AND status='done'",
+    )
+    .execute(&data.pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "archived": result.rows_affected() })))
+}
+
+async fn unarchive_task(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(current) = fetch_task(&mut tx, id).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    };
+    let task = Task {
+        archived: false,
+        ..current.clone()
+    };
+    let exceeded = check_wip(&mut tx, Some(&current), &task)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let warnings = match wip_outcome(exceeded) {
+        Ok(warnings) => warnings,
+        Err(reject) => return Ok(HttpResponse::UnprocessableEntity().json(reject)),
+    };
+    sqlx::query("UPDATE tasks SET archived=0 WHERE id=?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(TaskResponse { task, warnings }))
+}
+
+/// Deletes a task with its key aliases and cached embedding, and detaches its subtasks. Returns whether the task existed.
+async fn delete_task_rows(conn: &mut SqliteConnection, id: i64) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM tasks WHERE id=?").bind(id).execute(&mut *conn).await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    sqlx::query("DELETE FROM task_key_aliases WHERE task_id=?")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM task_embeddings WHERE task_id=?")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("UPDATE tasks SET parent_id=NULL WHERE parent_id=?")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(true)
+}
+
+async fn delete_task(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    if !delete_task_rows(&mut tx, id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    }
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Languages notes can be written in and tasks written in, by ISO 639-1 code.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("en", "English"), ("ja", "Japanese"), ("zh", "Chinese"), ("ko", "Korean"), ("de", "German"),
+    ("fr", "French"), ("es", "Spanish"), ("pt", "Portuguese"), ("it", "Italian"), ("nl", "Dutch"),
+    ("ru", "Russian"), ("el", "Greek"), ("ar", "Arabic"), ("he", "Hebrew"), ("hi", "Hindi"), ("th", "Thai"),
+];
+
+/// Frequent words that tell apart languages written in Latin script.
+const LANGUAGE_STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "to", "of", "is", "we", "will", "for", "with", "that"]),
+    ("de", &["der", "die", "und", "das", "ist", "wir", "nicht", "mit", "zu", "ein"]),
+    ("fr", &["le", "les", "et", "est", "nous", "pour", "des", "une", "avec", "pas"]),
+    ("es", &["el", "los", "y", "es", "que", "para", "con", "una", "por", "las"]),
+    ("pt", &["o", "os", "e", "é", "não", "para", "com", "uma", "do", "em"]),
+    ("it", &["il", "di", "che", "per", "non", "una", "con", "sono", "gli", "della"]),
+    ("nl", &["de", "het", "en", "een", "van", "wij", "niet", "met", "voor", "zijn"]),
+];
+
+/// The canonical code of a supported language, given its code in any case.
+fn language_code(code: &str) -> Option<&'static str> {
+    let code = code.trim();
+    LANGUAGES.iter().find(|(c, _)| c.eq_ignore_ascii_case(code)).map(|(c, _)| *c)
+}
+
+fn language_name(code: &str) -> &'static str {
+    LANGUAGES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, name)| *name)
+        .unwrap_or("English")
+}
+
+/// Guesses the language of `text` from its script and, for Latin script, from frequent
+/// words. `None` when there is too little to go on.
+fn detect_language(text: &str) -> Option<&'static str> {
+    let mut letters = 0usize;
+    let mut scripts: HashMap<&'static str, usize> = HashMap::new();
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        let script = match c as u32 {
+            0x3040..=0x30FF => "kana",
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF => "han",
+            0x1100..=0x11FF | 0xAC00..=0xD7AF => "ko",
+            0x0400..=0x04FF => "ru",
+            0x0370..=0x03FF => "el",
+            0x0600..=0x06FF => "ar",
+            0x0590..=0x05FF => "he",
+            0x0900..=0x097F => "hi",
+            0x0E00..=0x0E7F => "th",
+            _ => continue,
+        };
+        *scripts.entry(script).or_default() += 1;
+    }
+    if letters == 0 {
+        return None;
+    }
+    // Japanese mixes kana with kanji; kanji alone is Chinese.
+    let kana = scripts.remove("kana").unwrap_or(0);
+    let han = scripts.remove("han").unwrap_or(0);
+    if kana > 0 {
+        scripts.insert("ja", kana + han);
+    } else if han > 0 {
+        scripts.insert("zh", han);
+    }
+    if let Some((code, count)) = scripts.into_iter().max_by_key(|(_, count)| *count) {
+        if count * 5 >= letters {
+            return Some(code);
+        }
+    }
+
+    let words: Vec<String> = text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(String::from)
+        .collect();
+    let mut hits: Vec<(&'static str, usize)> = LANGUAGE_STOPWORDS
+        .iter()
+        .map(|(code, stopwords)| (*code, words.iter().filter(|w| stopwords.contains(&w.as_str())).count()))
+        .collect();
+    hits.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    match hits.as_slice() {
+        [(code, best), (_, second), ..] if *best >= 3 && best > second => Some(code),
+        _ => None,
+    }
+}
+
+/// Language handling for an extraction, sent with the notes or the meeting to extract.
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct LanguageOptions {
+    /// ISO 639-1 code of the notes; detected when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    /// Language to write the tasks in; defaults to `AI_OUTPUT_LANGUAGE`, then to the
+    /// language of the notes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output_language: Option<String>,
+    /// Keep the passage each task was extracted from, in the notes' language, in `notes`.
+    #[serde(default)]
+    keep_original: bool,
+}
+
+impl LanguageOptions {
+    fn validate(&self) -> Option<String> {
+        [&self.language, &self.output_language]
+            .into_iter()
+            .flatten()
+            .find(|code| language_code(code).is_none())
+            .map(|code| {
+                let codes: Vec<&str> = LANGUAGES.iter().map(|(c, _)| *c).collect();
+                format!("unknown language '{}'; expected one of: {}", code, codes.join(", "))
+            })
+    }
+}
+
+/// The languages an extraction works with, resolved from the request, the meeting and
+/// the environment.
+#[derive(Clone, Copy)]
+struct ExtractionLanguage {
+    /// Language of the notes, when given or detected.
+    notes: Option<&'static str>,
+    /// Language to write tasks in; `None` keeps the notes' language.
+    output: Option<&'static str>,
+    keep_original: bool,
+}
+
+impl ExtractionLanguage {
+    /// Instructions appended to the extraction prompt, so every template version handles
+    /// languages the same way.
+    fn instructions(&self) -> String {
+        let mut out = String::from("\n\nLanguage:\n");
+        if let Some(notes) = self.notes {
+            out.push_str(&format!("- The meeting notes are written in {}.\n", language_name(notes)));
+        }
+        match self.output {
+            Some(output) if Some(output) != self.notes => out.push_str(&format!(
+                "- Write every task's title, description and tags in {}, translating from the notes.\n",
+                language_name(output)
+            )),
+            Some(output) => out.push_str(&format!(
+                "- Write every task's title, description and tags in {}. Do not translate.\n",
+                language_name(output)
+            )),
+            None => out.push_str(
+                "- Write every task's title, description and tags in the language of the notes. Do not translate.\n",
+            ),
+        }
+        out.push_str("- Use project and assignee names exactly as listed above, whatever the language of the notes.\n");
+        if self.keep_original {
+            out.push_str(
+                "- Set \"source_text\" to the sentence or sentences of the notes each task comes from, copied verbatim.\n",
+            );
+        }
+        out
+    }
+
+    /// The task notes holding the passage a task was extracted from, within the 2000-byte
+    /// limit on notes.
+    fn original_note(&self, source_text: &str) -> String {
+        let label = match self.notes {
+            Some(code) => format!("Original ({}): ", language_name(code)),
+            None => "Original: ".to_string(),
+        };
+        let mut note = label;
+        for c in source_text.trim().chars() {
+            if note.len() + c.len_utf8() > 1990 {
+                note.push('…');
+                break;
+            }
+            note.push(c);
+        }
+        note
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct GenerateTasksRequest {
+    meeting_notes: String,
+    /// Defaults to the first line of the notes.
+    #[serde(default)]
+    title: Option<String>,
+    /// Meeting date (YYYY-MM-DD); defaults to today.
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default)]
+    attendees: Vec<String>,
+    #[serde(flatten)]
+    language: LanguageOptions,
+}
+
+#[derive(Serialize, Clone)]
+struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+impl ChatMessage {
+    fn system(content: impl Into<String>) -> Self {
+        ChatMessage {
+            role: "system".to_string(),
+            content: content.into(),
+        }
+    }
+
+    fn user(content: impl Into<String>) -> Self {
+        ChatMessage {
+            role: "user".to_string(),
+            content: content.into(),
+        }
+    }
+
+    fn assistant(content: impl Into<String>) -> Self {
+        ChatMessage {
+            role: "assistant".to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatChoiceMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatChoiceMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Option<Vec<ChatChoice>>,
+    error: Option<ChatError>,
+    usage: Option<TokenUsage>,
+}
+
+/// Token counts from the `usage` block of a chat completion or embedding response.
+#[derive(Deserialize, Clone, Copy, Default)]
+struct TokenUsage {
+    #[serde(default)]
+    prompt_tokens: i64,
+    #[serde(default)]
+    completion_tokens: i64,
+}
+
+/// The text of a completion and what it cost.
+struct Completion {
+    content: String,
+    /// Provider and model that answered; they differ from the configured ones after a fallback.
+    provider: String,
+    model: String,
+    /// `None` when the provider did not report usage.
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChatError {
+    message: String,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
+}
+
+/// Embedding vectors, one per input, and what they cost.
+struct Embeddings {
+    vectors: Vec<Vec<f32>>,
+    /// `None` when the provider did not report usage.
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Debug)]
+enum AiError {
+    /// The provider is missing required configuration (e.g. an API key).
+    NotConfigured(String),
+    /// The request could not be sent or the response could not be read.
+    Request(String),
+    /// The provider answered with an error status.
+    Upstream(String),
+    /// The provider is rate limiting (429) or failing (5xx); worth retrying later.
+    Unavailable(String),
+    /// The provider answered without any content.
+    EmptyResponse(String),
+    /// The provider's answer did not match the expected format, even after a repair attempt.
+    InvalidResponse(String),
+}
+
+impl AiError {
+    /// Missing configuration and an unavailable provider are `503`; anything the provider
+    /// got wrong is `502 Bad Gateway`.
+    fn into_response(self) -> HttpResponse {
+        let body = serde_json::json!({ "error": self.message() });
+        match self {
+            AiError::NotConfigured(_) | AiError::Unavailable(_) => HttpResponse::ServiceUnavailable().json(body),
+            _ => HttpResponse::BadGateway().json(body),
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            AiError::NotConfigured(msg)
+            | AiError::Request(msg)
+            | AiError::Upstream(msg)
+            | AiError::Unavailable(msg)
+            | AiError::EmptyResponse(msg)
+            | AiError::InvalidResponse(msg) => msg,
+        }
+    }
+}
+
+/// A chat-completion backend used for every AI feature.
+#[async_trait]
+trait AiProvider: Send + Sync {
+    /// Human-readable provider name used in error messages.
+    fn name(&self) -> &str;
+    /// Model used for completions.
+    fn model(&self) -> &str;
+    /// Sends the conversation and returns the text of the first choice.
+    async fn complete(&self, messages: &[ChatMessage]) -> Result<Completion, AiError>;
+    /// Like `complete`, but asks for JSON matching `schema`. Providers without a
+    /// structured-output mode rely on the prompt alone.
+    async fn complete_json(
+        &self,
+        messages: &[ChatMessage],
+        _schema_name: &str,
+        _schema: &serde_json::Value,
+    ) -> Result<Completion, AiError> {
+        self.complete(messages).await
+    }
+    /// Embedding model used by `embed`, or `None` when the provider cannot embed text.
+    fn embedding_model(&self) -> Option<&str> {
+        None
+    }
+    /// `false` while calls are being failed fast because the provider keeps failing.
+    fn available(&self) -> bool {
+        true
+    }
+    /// Returns one embedding vector per input.
+    async fn embed(&self, _inputs: &[String]) -> Result<Embeddings, AiError> {
+        Err(AiError::NotConfigured(format!("{} does not support embeddings", self.name())))
+    }
+}
+
+/// Any server exposing the OpenAI `/chat/completions` API: xAI, Gemini's OpenAI
+/// endpoint, or a self-hosted model behind Ollama, vLLM or llama.cpp server.
+struct OpenAiCompatibleProvider {
+    name: String,
+    base_url: String,
+    model: String,
+    /// Set from `*_EMBEDDING_MODEL`; enables embedding-based duplicate detection.
+    embedding_model: Option<String>,
+    /// Send a JSON schema as `response_format`; disabled with `*_STRUCTURED_OUTPUT=false`
+    /// for servers that reject it.
+    structured_output: bool,
+    api_key: Option<String>,
+    /// Environment variable named in the error when a required key is missing.
+    api_key_var: Option<&'static str>,
+    http: AiHttp,
+}
+
+impl OpenAiCompatibleProvider {
+    fn xai(http: &AiHttp) -> Self {
+        OpenAiCompatibleProvider {
+            name: "xAI".to_string(),
+            base_url: XAI_BASE_URL.to_string(),
+            model: std::env::var("XAI_MODEL").unwrap_or_else(|_| XAI_MODEL_DEFAULT.to_string()),
+            embedding_model: std::env::var("XAI_EMBEDDING_MODEL").ok().filter(|m| !m.is_empty()),
+            structured_output: env_flag("XAI_STRUCTURED_OUTPUT", true),
+            api_key: std::env::var("XAI_API_KEY").ok().filter(|k| !k.is_empty()),
+            api_key_var: Some("XAI_API_KEY"),
+            http: http.clone(),
+        }
+    }
+
+    fn gemini(http: &AiHttp) -> Self {
+        OpenAiCompatibleProvider {
+            name: "Gemini".to_string(),
+            base_url: GEMINI_BASE_URL.to_string(),
+            model: std::env::var("GEMINI_MODEL").unwrap_or_else(|_| GEMINI_MODEL_DEFAULT.to_string()),
+            embedding_model: std::env::var("GEMINI_EMBEDDING_MODEL").ok().filter(|m| !m.is_empty()),
+            structured_output: env_flag("GEMINI_STRUCTURED_OUTPUT", true),
+            api_key: std::env::var("GEMINI_API_KEY").ok().filter(|k| !k.is_empty()),
+            api_key_var: Some("GEMINI_API_KEY"),
+            http: http.clone(),
+        }
+    }
+
+    /// Self-hosted or third-party endpoint configured with `OPENAI_BASE_URL`,
+    /// `OPENAI_MODEL` and an optional `OPENAI_API_KEY`.
+    fn openai_compatible(http: &AiHttp) -> Self {
+        OpenAiCompatibleProvider {
+            name: "OpenAI-compatible".to_string(),
+            base_url: std::env::var("OPENAI_BASE_URL").unwrap_or_default(),
+            model: std::env::var("OPENAI_MODEL").unwrap_or_default(),
+            embedding_model: std::env::var("OPENAI_EMBEDDING_MODEL").ok().filter(|m| !m.is_empty()),
+            structured_output: env_flag("OPENAI_STRUCTURED_OUTPUT", true),
+            api_key: std::env::var("OPENAI_API_KEY").ok().filter(|k| !k.is_empty()),
+            api_key_var: None,
+            http: http.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl AiProvider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, messages: &[ChatMessage]) -> Result<Completion, AiError> {
+        self.chat(messages, None).await
+    }
+
+    async fn complete_json(
+        &self,
+        messages: &[ChatMessage],
+        schema_name: &str,
+        schema: &serde_json::Value,
+    ) -> Result<Completion, AiError> {
+        if !self.structured_output {
+            return self.chat(messages, None).await;
+        }
+        let response_format = serde_json::json!({
+            "type": "json_schema",
+            "json_schema": { "name": schema_name, "strict": true, "schema": schema }
+        });
+        self.chat(messages, Some(response_format)).await
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        self.embedding_model.as_deref()
+    }
+
+    async fn embed(&self, inputs: &[String]) -> Result<Embeddings, AiError> {
+        let Some(model) = &self.embedding_model else {
+            return Err(AiError::NotConfigured(format!("No embedding model is configured for {}", self.name)));
+        };
+        let body_text = self.post("embeddings", &EmbeddingRequest { model, input: inputs }).await?;
+        let mut resp: EmbeddingResponse = serde_json::from_str(&body_text)
+            .map_err(|e| AiError::Request(format!("{} embedding response could not be read: {}", self.name, e)))?;
+        resp.data.sort_by_key(|d| d.index);
+        Ok(Embeddings {
+            vectors: resp.data.into_iter().map(|d| d.embedding).collect(),
+            usage: resp.usage,
+        })
+    }
+}
+
+impl OpenAiCompatibleProvider {
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        response_format: Option<serde_json::Value>,
+    ) -> Result<Completion, AiError> {
+        let body_text = self
+            .post(
+                "chat/completions",
+                &ChatRequest {
+                    model: &self.model,
+                    messages,
+                    response_format,
+                },
+            )
+            .await?;
+        let chat_resp: ChatResponse = serde_json::from_str(&body_text)
+            .map_err(|e| AiError::Request(format!("{} API response could not be read: {}", self.name, e)))?;
+        let content = chat_resp
+            .choices
+            .and_then(|c| c.into_iter().next())
+            .and_then(|c| c.message.content)
+            .ok_or_else(|| AiError::EmptyResponse(format!("No content in {} response", self.name)))?;
+        Ok(Completion {
+            content,
+            provider: self.name.clone(),
+            model: self.model.clone(),
+            usage: chat_resp.usage,
+        })
+    }
+
+    /// Posts `body` to `{base_url}/{path}` and returns the body of a successful response.
+    async fn post<T: Serialize + Sync>(&self, path: &str, body: &T) -> Result<String, AiError> {
+        if let (None, Some(var)) = (&self.api_key, self.api_key_var) {
+            return Err(AiError::NotConfigured(format!(
+                "{} is not configured. Please set the environment variable.",
+                var
+            )));
+        }
+        if self.base_url.is_empty() || self.model.is_empty() {
+            return Err(AiError::NotConfigured(
+                "OPENAI_BASE_URL and OPENAI_MODEL must be set. Please set the environment variables.".to_string(),
+            ));
+        }
+
+        let url = format!("{}/{}", self.base_url.trim_end_matches('/'), path);
+        let mut attempt = 0;
+        loop {
+            let mut req = self
+                .http
+                .client
+                .post(&url)
+                .timeout(self.http.timeout)
+                .header("Content-Type", "application/json")
+                .json(body);
+            if let Some(key) = &self.api_key {
+                req = req.header("Authorization", format!("Bearer {}", key));
+            }
+            let resp = match req.send().await {
+                Ok(resp) => resp,
+                Err(e) if (e.is_connect() || e.is_timeout()) && attempt < self.http.max_retries => {
+                    tokio::time::sleep(AiHttp::backoff(attempt, None)).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(AiError::Request(format!("{} API request failed: {}", self.name, e))),
+            };
+
+            let status = resp.status();
+            let retryable = status.as_u16() == 429 || status.is_server_error();
+            if retryable && attempt < self.http.max_retries {
+                let delay = AiHttp::backoff(attempt, resp.headers().get(reqwest::header::RETRY_AFTER));
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+            let body_text = resp
+                .text()
+                .await
+                .map_err(|e| AiError::Request(format!("{} API response could not be read: {}", self.name, e)))?;
+
+            if !status.is_success() {
+                let err_msg = serde_json::from_str::<ChatResponse>(&body_text)
+                    .ok()
+                    .and_then(|r| r.error)
+                    .map(|e| e.message)
+                    .unwrap_or_else(|| format!("{} API error: {} - {}", self.name, status, body_text));
+                if retryable {
+                    return Err(AiError::Unavailable(err_msg));
+                }
+                return Err(AiError::Upstream(err_msg));
+            }
+            return Ok(body_text);
+        }
+    }
+}
+
+/// Deterministic provider for tests and offline development. Returns `AI_FAKE_RESPONSE`
+/// verbatim when set; otherwise turns each bullet line of the last user message into a task.
+/// With `AI_FAKE_EMBEDDINGS=1` it also embeds text as hashed word counts.
+struct FakeProvider {
+    model: String,
+    response: Option<String>,
+    embeddings: bool,
+}
+
+#[async_trait]
+impl AiProvider for FakeProvider {
+    fn name(&self) -> &str {
+        "Fake"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, messages: &[ChatMessage]) -> Result<Completion, AiError> {
+        let completion = |content: String| Completion {
+            content,
+            provider: "Fake".to_string(),
+            model: self.model.clone(),
+            usage: None,
+        };
+        if let Some(response) = &self.response {
+            return Ok(completion(response.clone()));
+        }
+        let input = messages.iter().rev().find(|m| m.role == "user").map(|m| m.content.as_str()).unwrap_or("");
+        let tasks: Vec<serde_json::Value> = input
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("- ").or_else(|| line.trim().strip_prefix("* ")))
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                serde_json::json!({
+                    "title": line,
+                    "description": line,
+                    "tags": ["ai-generated"],
+                    "deadline": null,
+                    "project": "General",
+                    "assignee": "Unassigned",
+                    "status": "todo",
+                })
+            })
+            .collect();
+        Ok(completion(serde_json::Value::Array(tasks).to_string()))
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        self.embeddings.then_some("fake")
+    }
+
+    async fn embed(&self, inputs: &[String]) -> Result<Embeddings, AiError> {
+        let vectors = inputs
+            .iter()
+            .map(|input| {
+                let mut vector = vec![0.0; 64];
+                for token in similarity_tokens(input) {
+                    let hash = token.bytes().fold(2166136261u32, |h, b| (h ^ b as u32).wrapping_mul(16777619));
+                    vector[hash as usize % 64] += 1.0;
+                }
+                vector
+            })
+            .collect();
+        Ok(Embeddings { vectors, usage: None })
+    }
+}
+
+/// HTTP client shared by every provider, with the per-request timeout and retry policy.
+#[derive(Clone)]
+struct AiHttp {
+    client: reqwest::Client,
+    /// `AI_TIMEOUT_SECS` (default 120): bounds each request, including reading the body.
+    timeout: std::time::Duration,
+    /// `AI_MAX_RETRIES` (default 2): retries after a 429/5xx answer or a connection failure.
+    max_retries: u32,
+}
+
+impl AiHttp {
+    fn from_env() -> Self {
+        let timeout = std::env::var("AI_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(120);
+        let max_retries = std::env::var("AI_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2);
+        AiHttp {
+            client: reqwest::Client::builder()
+                .connect_timeout(std::time::Duration::from_secs(10))
+                .pool_idle_timeout(std::time::Duration::from_secs(90))
+                .build()
+                .unwrap_or_else(|_| reqwest::Client::new()),
+            timeout: std::time::Duration::from_secs(timeout),
+            max_retries,
+        }
+    }
+
+    /// Delay before retry number `attempt` (0-based): the server's `Retry-After` in seconds
+    /// when present (capped at 60s), otherwise 500ms doubling per attempt up to 30s.
+    fn backoff(attempt: u32, retry_after: Option<&reqwest::header::HeaderValue>) -> std::time::Duration {
+        if let Some(secs) = retry_after
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+        {
+            return std::time::Duration::from_secs(secs.min(60));
+        }
+        std::time::Duration::from_millis((500u64 << attempt.min(6)).min(30_000))
+    }
+}
+
+/// Counts consecutive provider failures; once `threshold` is reached, calls fail fast
+/// until `cooldown` has passed, after which one call is let through to probe the provider.
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: std::time::Duration,
+    state: std::sync::Mutex<(u32, Option<std::time::Instant>)>,
+}
+
+impl CircuitBreaker {
+    /// `AI_CIRCUIT_THRESHOLD` (default 5) failures open the circuit for
+    /// `AI_CIRCUIT_COOLDOWN_SECS` (default 60).
+    fn from_env() -> Self {
+        CircuitBreaker {
+            threshold: std::env::var("AI_CIRCUIT_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(5),
+            cooldown: std::time::Duration::from_secs(
+                std::env::var("AI_CIRCUIT_COOLDOWN_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(60),
+            ),
+            state: std::sync::Mutex::new((0, None)),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.1.is_some_and(|until| std::time::Instant::now() < until)
+    }
+
+    /// Records the outcome of a call. Only failures that suggest the provider is down
+    /// count; a bad request or an unparseable answer does not.
+    fn record<T>(&self, result: &Result<T, AiError>) {
+        let mut state = self.state.lock().unwrap();
+        match result {
+            Ok(_) => *state = (0, None),
+            Err(AiError::Request(_)) | Err(AiError::Unavailable(_)) => {
+                state.0 += 1;
+                if state.0 >= self.threshold {
+                    state.1 = Some(std::time::Instant::now() + self.cooldown);
+                }
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Wraps the configured provider with a circuit breaker and, when `AI_FALLBACK_PROVIDER`
+/// is set, a secondary provider used when the primary is unavailable.
+struct GuardedProvider {
+    primary: Box<dyn AiProvider>,
+    breaker: CircuitBreaker,
+    fallback: Option<Box<GuardedProvider>>,
+}
+
+impl GuardedProvider {
+    fn new(primary: Box<dyn AiProvider>, fallback: Option<Box<dyn AiProvider>>) -> Self {
+        GuardedProvider {
+            primary,
+            breaker: CircuitBreaker::from_env(),
+            fallback: fallback.map(|f| {
+                Box::new(GuardedProvider {
+                    primary: f,
+                    breaker: CircuitBreaker::from_env(),
+                    fallback: None,
+                })
+            }),
+        }
+    }
+
+    fn circuit_open_error(&self) -> AiError {
+        AiError::Unavailable(format!(
+            "{} is temporarily unavailable after repeated failures",
+            self.primary.name()
+        ))
+    }
+}
+
+/// Whether an error from the primary provider should be retried on the fallback.
+fn should_fall_back(error: &AiError) -> bool {
+    matches!(error, AiError::Request(_) | AiError::Unavailable(_) | AiError::NotConfigured(_))
+}
+
+#[async_trait]
+impl AiProvider for GuardedProvider {
+    fn name(&self) -> &str {
+        self.primary.name()
+    }
+
+    fn model(&self) -> &str {
+        self.primary.model()
+    }
+
+    fn available(&self) -> bool {
+        !self.breaker.is_open() || self.fallback.as_ref().is_some_and(|f| f.available())
+    }
+
+    async fn complete(&self, messages: &[ChatMessage]) -> Result<Completion, AiError> {
+        let result = if self.breaker.is_open() {
+            Err(self.circuit_open_error())
+        } else {
+            let result = self.primary.complete(messages).await;
+            self.breaker.record(&result);
+            result
+        };
+        match (result, &self.fallback) {
+            (Err(e), Some(fallback)) if should_fall_back(&e) => fallback.complete(messages).await,
+            (result, _) => result,
+        }
+    }
+
+    async fn complete_json(
+        &self,
+        messages: &[ChatMessage],
+        schema_name: &str,
+        schema: &serde_json::Value,
+    ) -> Result<Completion, AiError> {
+        let result = if self.breaker.is_open() {
+            Err(self.circuit_open_error())
+        } else {
+            let result = self.primary.complete_json(messages, schema_name, schema).await;
+            self.breaker.record(&result);
+            result
+        };
+        match (result, &self.fallback) {
+            (Err(e), Some(fallback)) if should_fall_back(&e) => {
+                fallback.complete_json(messages, schema_name, schema).await
+            }
+            (result, _) => result,
+        }
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        self.primary.embedding_model()
+    }
+
+    /// Embeddings never fall back: vectors from another model are not comparable with
+    /// the cached ones.
+    async fn embed(&self, inputs: &[String]) -> Result<Embeddings, AiError> {
+        if self.breaker.is_open() {
+            return Err(self.circuit_open_error());
+        }
+        let result = self.primary.embed(inputs).await;
+        self.breaker.record(&result);
+        result
+    }
+}
+
+/// Reads a boolean environment variable (`1`/`true` or `0`/`false`).
+fn env_flag(name: &str, default: bool) -> bool {
+    match std::env::var(name).map(|v| v.to_lowercase()) {
+        Ok(v) if v == "1" || v == "true" => true,
+        Ok(v) if v == "0" || v == "false" => false,
+        _ => default,
+    }
+}
+
+/// Builds a provider by name: `gemini`, `xai`, `openai` (any OpenAI-compatible endpoint) or
+/// `fake`, optionally overriding its configured model. Returns `None` for unknown names.
+fn build_ai_provider(provider: &str, model: Option<&str>, http: &AiHttp) -> Option<Box<dyn AiProvider>> {
+    let mut openai_compatible = match provider.to_lowercase().as_str() {
+        "gemini" => OpenAiCompatibleProvider::gemini(http),
+        "xai" => OpenAiCompatibleProvider::xai(http),
+        "openai" => OpenAiCompatibleProvider::openai_compatible(http),
+        "fake" => {
+            return Some(Box::new(FakeProvider {
+                model: model.unwrap_or("fake").to_string(),
+                response: std::env::var("AI_FAKE_RESPONSE").ok(),
+                embeddings: env_flag("AI_FAKE_EMBEDDINGS", false),
+            }))
+        }
+        _ => return None,
+    };
+    if let Some(model) = model {
+        openai_compatible.model = model.to_string();
+    }
+    Some(Box::new(openai_compatible))
+}
+
+/// Builds the provider selected by `AI_PROVIDER` (defaulting to `gemini`) behind a circuit
+/// breaker, with `AI_FALLBACK_PROVIDER` as its fallback when set.
+fn resolve_ai_provider(http: &AiHttp) -> GuardedProvider {
+    let provider = std::env::var("AI_PROVIDER").unwrap_or_else(|_| "gemini".to_string());
+    let primary = build_ai_provider(&provider, None, http)
+        .or_else(|| build_ai_provider("gemini", None, http))
+        .expect("gemini provider is always available");
+    let fallback = std::env::var("AI_FALLBACK_PROVIDER")
+        .ok()
+        .filter(|f| !f.is_empty() && !f.eq_ignore_ascii_case(&provider))
+        .and_then(|f| build_ai_provider(&f, None, http));
+    GuardedProvider::new(primary, fallback)
+}
+
+fn extract_json_from_response(text: &str) -> Option<&str> {
+    let text = text.trim();
+    if let Some(start) = text.find("```json") {
+        let content_start = text[start..].find('\n').map(|i| start + i + 1).unwrap_or(start + 7);
+        if let Some(end) = text[content_start..].find("```") {
+            return Some(text[content_start..content_start + end].trim());
+        }
+    }
+    if let Some(start) = text.find("```") {
+        let content_start = text[start..].find('\n').map(|i| start + i + 1).unwrap_or(start + 3);
+        if let Some(end) = text[content_start..].find("```") {
+            return Some(text[content_start..content_start + end].trim());
+        }
+    }
+    if text.starts_with('[') || text.starts_with('{') {
+        return Some(text);
+    }
+    None
+}
+
+/// JSON schema of the extraction response, mirroring the fields of `Task` the model fills in,
+/// plus `source_text` when the original passages are kept and `speaker` for transcripts. The
+/// tasks are wrapped in an object because structured-output modes require an object root.
+fn task_extraction_schema(source_text: bool, speaker: bool) -> serde_json::Value {
+    let mut schema = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "tasks": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "title": { "type": "string" },
+                        "description": { "type": "string" },
+                        "tags": { "type": "array", "items": { "type": "string" } },
+                        "deadline": {
+                            "type": ["string", "null"],
+                            "description": "YYYY-MM-DD, or a relative deadline copied from the notes"
+                        },
+                        "project": { "type": "string" },
+                        "assignee": { "type": "string" },
+                        "status": { "type": "string", "enum": VALID_STATUSES }
+                    },
+                    "required": ["title", "description", "tags", "deadline", "project", "assignee", "status"],
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": ["tasks"],
+        "additionalProperties": false
+    });
+    let item = &mut schema["properties"]["tasks"]["items"];
+    if source_text {
+        item["properties"]["source_text"] = serde_json::json!({ "type": "string" });
+        if let Some(required) = item["required"].as_array_mut() {
+            required.push(serde_json::json!("source_text"));
+        }
+    }
+    if speaker {
+        item["properties"]["speaker"] = serde_json::json!({ "type": ["string", "null"] });
+        if let Some(required) = item["required"].as_array_mut() {
+            required.push(serde_json::json!("speaker"));
+        }
+    }
+    schema
+}
+
+/// Returns the extracted items, accepting the schema's `{"tasks": [...]}` as well as a bare array.
+fn parse_extraction(content: &str) -> std::result::Result<Vec<serde_json::Value>, String> {
+    let json_str = extract_json_from_response(content).unwrap_or(content.trim());
+    let value: serde_json::Value = serde_json::from_str(json_str).map_err(|e| e.to_string())?;
+    match value {
+        serde_json::Value::Array(items) => Ok(items),
+        serde_json::Value::Object(mut obj) => match obj.remove("tasks") {
+            Some(serde_json::Value::Array(items)) => Ok(items),
+            _ => Err("expected an object with a \"tasks\" array".to_string()),
+        },
+        _ => Err("expected an object with a \"tasks\" array".to_string()),
+    }
+}
+
+/// Whether `s` is a real calendar date written as `YYYY-MM-DD`.
+fn is_iso_date(s: &str) -> bool {
+    s.len() == 10 && NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok()
+}
+
+/// Checks one extracted item against the schema. Missing optional fields get the defaults the
+/// prompt asks for; present fields of the wrong type are errors. Relative deadlines are resolved
+/// against `reference`; one that cannot be resolved is dropped.
+fn parse_task_item(
+    item: &serde_json::Value,
+    reference: NaiveDate,
+    config: &DeadlineConfig,
+) -> std::result::Result<Task, Vec<String>> {
+    let Some(obj) = item.as_object() else {
+        return Err(vec!["item must be an object".to_string()]);
+    };
+    let mut errors = Vec::new();
+    let mut string_field = |field: &str, default: Option<&str>| -> String {
+        match obj.get(field) {
+            Some(serde_json::Value::String(s)) => s.trim().to_string(),
+            None | Some(serde_json::Value::Null) => match default {
+                Some(d) => d.to_string(),
+                None => {
+                    errors.push(format!("{} is required", field));
+                    String::new()
+                }
+            },
+            Some(_) => {
+                errors.push(format!("{} must be a string", field));
+                String::new()
+            }
+        }
+    };
+    let title = string_field("title", None);
+    let description = string_field("description", Some(""));
+    let project = string_field("project", Some("General"));
+    let assignee = string_field("assignee", Some("Unassigned"));
+    let status = string_field("status", Some("todo"));
+    if title.is_empty() && obj.get("title").is_some_and(|v| v.is_string()) {
+        errors.push("title must not be empty".to_string());
+    }
+    let deadline = match obj.get("deadline") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(s)) if s.trim().is_empty() => None,
+        Some(serde_json::Value::String(s)) => {
+            resolve_deadline(s, reference, config).map(|date| date.format("%Y-%m-%d").to_string())
+        }
+        Some(_) => {
+            errors.push("deadline must be null or a string".to_string());
+            None
+        }
+    };
+    let tags = match obj.get("tags") {
+        None | Some(serde_json::Value::Null) => vec!["ai-generated".to_string()],
+        Some(serde_json::Value::Array(arr)) if arr.iter().all(|v| v.is_string()) => {
+            arr.iter().filter_map(|v| v.as_str().map(String::from)).collect()
+        }
+        Some(_) => {
+            errors.push("tags must be an array of strings".to_string());
+            Vec::new()
+        }
+    };
+
+    let task = Task {
+        id: 0,
+        title,
+        description,
+        tags,
+        deadline,
+        project,
+        assignee,
+        status,
+        in_sprint: false,
+        notes: None,
+        archived: false,
+        rank: String::new(),
+        key: None,
+        meeting_id: None,
+        prompt_version: None,
+        parent_id: None,
+        estimate_hours: None,
+    };
+    if errors.is_empty() {
+        if let Some(msg) = validate_task(&task) {
+            errors.push(msg.to_string());
+        }
+    }
+    if errors.is_empty() {
+        Ok(task)
+    } else {
+        Err(errors)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct GenerateTasksQuery {
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    on_duplicate: DuplicateMode,
+    #[serde(default)]
+    threshold: Option<f64>,
+}
+
+/// An extracted item that did not match the schema, reported instead of being created.
+#[derive(Serialize, Deserialize)]
+struct RejectedItem {
+    /// Chunk of the notes the item was extracted from, when the notes were split.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chunk: Option<usize>,
+    index: usize,
+    item: serde_json::Value,
+    errors: Vec<String>,
+}
+
+struct ProposedTasks {
+    tasks: Vec<Task>,
+    rejected: Vec<RejectedItem>,
+    names: Vec<NameResolution>,
+    deadlines: Vec<DeadlineResolution>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct PromptTemplate {
+    name: String,
+    version: i64,
+    template: String,
+    /// Team conventions substituted for `{{conventions}}`.
+    conventions: String,
+    /// Written from the built-in text rather than through `PUT /admin/prompts/{name}`.
+    builtin: bool,
+    created_at: String,
+}
+
+/// Stores each built-in template as a new version when its text differs from the latest
+/// version, so upgrades reach existing databases. A latest version written through the admin
+/// endpoint is kept.
+async fn seed_prompt_templates(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    for (name, template) in PROMPT_TEMPLATES {
+        let latest: Option<(i64, String, String, bool)> = sqlx::query_as(
+            "SELECT version, template, conventions, builtin FROM prompt_templates \
+             WHERE name=? ORDER BY version DESC LIMIT 1",
+        )
+        .bind(name)
+        .fetch_optional(pool)
+        .await?;
+        let (version, conventions) = match latest {
+            None => (1, String::new()),
+            Some((version, text, conventions, true)) if text != *template => (version + 1, conventions),
+            Some(_) => continue,
+        };
+        sqlx::query(
+            "INSERT OR IGNORE INTO prompt_templates (name, version, template, conventions, builtin) \
+             VALUES (?, ?, ?, ?, 1)",
+        )
+        .bind(name)
+        .bind(version)
+        .bind(template)
+        .bind(&conventions)
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// The latest version of the template `name`.
+async fn latest_prompt(pool: &SqlitePool, name: &str) -> Result<Option<PromptTemplate>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT name, version, template, conventions, builtin, created_at FROM prompt_templates \
+         WHERE name=? ORDER BY version DESC LIMIT 1",
+    )
+    .bind(name)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Replaces each `{{name}}` in `template` with its value.
+fn render_prompt(template: &str, vars: &[(&str, String)]) -> String {
+    vars.iter()
+        .fold(template.to_string(), |out, (name, value)| out.replace(&format!("{{{{{}}}}}", name), value))
+}
+
+/// Names of `{{...}}` placeholders in `template` that are not in `PROMPT_VARIABLES`.
+fn unknown_prompt_variables(template: &str) -> Vec<String> {
+    let mut unknown = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else { break };
+        let name = rest[start + 2..start + 2 + end].trim();
+        if !PROMPT_VARIABLES.contains(&name) && !unknown.iter().any(|u| u == name) {
+            unknown.push(name.to_string());
+        }
+        rest = &rest[start + 2 + end + 2..];
+    }
+    unknown
+}
+
+/// Common English nicknames and the full first name they stand for.
+const NICKNAMES: &[(&str, &str)] = &[
+    ("alex", "alexander"), ("andy", "andrew"), ("ben", "benjamin"), ("bill", "william"), ("bob", "robert"),
+    ("bobby", "robert"), ("chris", "christopher"), ("dan", "daniel"), ("dave", "david"), ("ed", "edward"),
+    ("greg", "gregory"), ("jen", "jennifer"), ("jess", "jessica"), ("jim", "james"), ("jimmy", "james"),
+    ("joe", "joseph"), ("jon", "jonathan"), ("kate", "katherine"), ("katie", "katherine"), ("liz", "elizabeth"),
+    ("beth", "elizabeth"), ("matt", "matthew"), ("meg", "margaret"), ("mike", "michael"), ("nick", "nicholas"),
+    ("rob", "robert"), ("sam", "samuel"), ("steve", "steven"), ("sue", "susan"), ("tom", "thomas"),
+    ("tony", "anthony"), ("will", "william"),
+];
+
+/// A project or assignee the model may refer to, with the other names it answers to
+/// (a project's key, an assignee's aliases).
+struct KnownName {
+    name: String,
+    aliases: Vec<String>,
+}
+
+impl KnownName {
+    /// The name followed by its aliases in parentheses, as listed in the prompt.
+    fn describe(&self) -> String {
+        if self.aliases.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} ({})", self.name, self.aliases.join(", "))
+        }
+    }
+}
+
+/// How a returned name was matched to an existing one.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum NameMatch {
+    /// Same name apart from case, spacing or punctuation.
+    Case,
+    /// A project key or an assignee alias.
+    Alias,
+    /// A nickname of the first name, e.g. "Bob" for "Robert Smith".
+    Nickname,
+    /// Part of the name, e.g. "Smith" for "Robert Smith".
+    Partial,
+    /// A close misspelling.
+    Typo,
+}
+
+enum ResolvedName {
+    Exact,
+    Matched(String, NameMatch),
+    Ambiguous(Vec<String>),
+    Unresolved,
+}
+
+fn normalize_name(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn canonical_first_name(token: &str) -> &str {
+    NICKNAMES
+        .iter()
+        .find(|(nick, _)| *nick == token)
+        .map(|(_, full)| *full)
+        .unwrap_or(token)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            cur.push((prev[j] + usize::from(ca != *cb)).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Matches `returned` against `known` by exact name, then case-insensitively, by alias,
+/// by name parts (each part equal, a nickname of, or the initial of a part of the known
+/// name), and finally allowing up to two typos in names of five or more characters.
+fn resolve_name(known: &[KnownName], returned: &str) -> ResolvedName {
+    if known.iter().any(|k| k.name == returned) {
+        return ResolvedName::Exact;
+    }
+    let wanted = normalize_name(returned);
+    if wanted.is_empty() {
+        return ResolvedName::Unresolved;
+    }
+    let pick = |matches: Vec<&KnownName>, how: NameMatch| match matches.as_slice() {
+        [] => None,
+        [one] => Some(ResolvedName::Matched(one.name.clone(), how)),
+        many => Some(ResolvedName::Ambiguous(many.iter().map(|k| k.name.clone()).collect())),
+    };
+
+    let by_case: Vec<_> = known.iter().filter(|k| normalize_name(&k.name) == wanted).collect();
+    if let Some(resolved) = pick(by_case, NameMatch::Case) {
+        return resolved;
+    }
+    let by_alias: Vec<_> = known
+        .iter()
+        .filter(|k| k.aliases.iter().any(|a| normalize_name(a) == wanted))
+        .collect();
+    if let Some(resolved) = pick(by_alias, NameMatch::Alias) {
+        return resolved;
+    }
+
+    let wanted_tokens: Vec<&str> = wanted.split(' ').collect();
+    // How each wanted part matches this name: `Nickname` when any part needed one.
+    let by_part = |k: &KnownName| -> Option<NameMatch> {
+        let name = normalize_name(&k.name);
+        let tokens: Vec<&str> = name.split(' ').collect();
+        let mut how = NameMatch::Partial;
+        for w in &wanted_tokens {
+            if tokens.iter().any(|t| t == w || (w.len() == 1 && t.starts_with(w))) {
+                continue;
+            }
+            if tokens.iter().any(|t| canonical_first_name(w) == canonical_first_name(t)) {
+                how = NameMatch::Nickname;
+                continue;
+            }
+            return None;
+        }
+        Some(how)
+    };
+    let by_parts: Vec<(&KnownName, NameMatch)> = known.iter().filter_map(|k| by_part(k).map(|how| (k, how))).collect();
+    match by_parts.as_slice() {
+        [] => {}
+        [(one, how)] => return ResolvedName::Matched(one.name.clone(), *how),
+        many => return ResolvedName::Ambiguous(many.iter().map(|(k, _)| k.name.clone()).collect()),
+    }
+
+    if wanted.chars().count() >= 5 {
+        let distances: Vec<(usize, &KnownName)> = known
+            .iter()
+            .map(|k| (edit_distance(&wanted, &normalize_name(&k.name)), k))
+            .filter(|(d, _)| *d <= 2)
+            .collect();
+        if let Some(best) = distances.iter().map(|(d, _)| *d).min() {
+            let closest = distances.into_iter().filter(|(d, _)| *d == best).map(|(_, k)| k).collect();
+            if let Some(resolved) = pick(closest, NameMatch::Typo) {
+                return resolved;
+            }
+        }
+    }
+    ResolvedName::Unresolved
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum NameStatus {
+    /// Matched to an existing name that differs from the one returned.
+    Remapped,
+    /// No existing name matched; the default was used.
+    Unresolved,
+    /// Several existing names matched equally well; the default was used.
+    Ambiguous,
+    /// No assignee was given; the one suggested by similar past tasks was used.
+    Suggested,
+    /// No assignee was given; the task was taken on by a transcript speaker matched to this assignee.
+    Speaker,
+}
+
+/// A project or assignee name returned by the model that was not used as is.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct NameResolution {
+    /// `project`, `assignee` or `speaker`.
+    field: String,
+    returned: String,
+    /// The name stored on the tasks.
+    used: String,
+    status: NameStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    method: Option<NameMatch>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    candidates: Vec<String>,
+    /// Confidence of a `suggested` assignee.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    confidence: Option<f64>,
+    /// Titles of the tasks that carried the name.
+    tasks: Vec<String>,
+}
+
+/// Adds `resolution` to `names`, merging it with an earlier report of the same name.
+fn record_name_resolution(names: &mut Vec<NameResolution>, resolution: NameResolution) {
+    match names
+        .iter_mut()
+        .find(|n| n.field == resolution.field && n.returned == resolution.returned && n.used == resolution.used)
+    {
+        Some(existing) => {
+            for title in resolution.tasks {
+                if !existing.tasks.contains(&title) {
+                    existing.tasks.push(title);
+                }
+            }
+        }
+        None => names.push(resolution),
+    }
+}
+
+/// A deadline the model returned as a relative expression.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct DeadlineResolution {
+    returned: String,
+    /// The date stored on the tasks; `None` when the expression could not be resolved.
+    resolved: Option<String>,
+    tasks: Vec<String>,
+}
+
+/// Adds `resolution` to `deadlines`, merging it with an earlier report of the same expression.
+fn record_deadline_resolution(deadlines: &mut Vec<DeadlineResolution>, resolution: DeadlineResolution) {
+    match deadlines
+        .iter_mut()
+        .find(|d| d.returned == resolution.returned && d.resolved == resolution.resolved)
+    {
+        Some(existing) => {
+            for title in resolution.tasks {
+                if !existing.tasks.contains(&title) {
+                    existing.tasks.push(title);
+                }
+            }
+        }
+        None => deadlines.push(resolution),
+    }
+}
+
+/// Replaces `value` with the existing name it refers to, or `default` when none matches,
+/// and reports the change.
+fn resolve_task_name(
+    names: &mut Vec<NameResolution>,
+    known: &[KnownName],
+    field: &str,
+    default: &str,
+    value: &mut String,
+    title: &str,
+) {
+    let (used, status, method, candidates) = match resolve_name(known, value) {
+        ResolvedName::Exact => return,
+        ResolvedName::Matched(name, how) => (name, NameStatus::Remapped, Some(how), Vec::new()),
+        ResolvedName::Ambiguous(candidates) => (default.to_string(), NameStatus::Ambiguous, None, candidates),
+        ResolvedName::Unresolved => (default.to_string(), NameStatus::Unresolved, None, Vec::new()),
+    };
+    let returned = std::mem::replace(value, used.clone());
+    record_name_resolution(
+        names,
+        NameResolution {
+            field: field.to_string(),
+            returned,
+            used,
+            status,
+            method,
+            candidates,
+            confidence: None,
+            tasks: vec![title.to_string()],
+        },
+    );
+}
+
+/// Line labels that look like speaker names in notes but are headings.
+const NON_SPEAKER_LABELS: &[&str] = &[
+    "action", "action item", "action items", "agenda", "answer", "attendees", "blocker", "blockers", "date",
+    "decision", "decisions", "done", "fyi", "goal", "goals", "next steps", "note", "notes", "question", "re", "risk",
+    "risks", "status", "subject", "summary", "title", "todo", "topic", "update", "updates",
+];
+
+/// A person speaking in transcript-style notes (`Alice: I'll handle the migration`) and the
+/// existing assignee they were matched to.
+struct Speaker {
+    /// The name as written in the notes.
+    label: String,
+    assignee: Option<String>,
+    method: Option<NameMatch>,
+    /// Assignees the name matched equally well.
+    candidates: Vec<String>,
+}
+
+impl Speaker {
+    /// The report of a speaker who matched no assignee, with the tasks they took on.
+    fn unmatched(&self, tasks: Vec<String>) -> NameResolution {
+        NameResolution {
+            field: "speaker".to_string(),
+            returned: self.label.clone(),
+            used: "Unassigned".to_string(),
+            status: if self.candidates.is_empty() {
+                NameStatus::Unresolved
+            } else {
+                NameStatus::Ambiguous
+            },
+            method: None,
+            candidates: self.candidates.clone(),
+            confidence: None,
+            tasks,
+        }
+    }
+}
+
+/// Whether `label` names one of `names` outright: exactly, apart from case, or by an alias.
+fn names_known(names: &[KnownName], label: &str) -> bool {
+    matches!(
+        resolve_name(names, label),
+        ResolvedName::Exact | ResolvedName::Matched(_, NameMatch::Case | NameMatch::Alias)
+    )
+}
+
+/// The speakers of `notes` in order of first appearance, matched to `assignees`. A line is a
+/// turn when it starts with a name (`Alice:`, `[Alice]`), also inside a list item. Labels that
+/// name a project or project key (`Backend: deploy tomorrow`) are not names. Notes are a
+/// transcript only when at least two turns make up at least half of their non-empty lines;
+/// otherwise they have no speakers.
+fn transcript_speakers(notes: &str, projects: &[KnownName], assignees: &[KnownName]) -> Vec<Speaker> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut turns = 0;
+    let mut lines = 0;
+    for line in notes.lines() {
+        let line = line.trim_start().trim_start_matches(['-', '*', '•']).trim_start();
+        if line.is_empty() {
+            continue;
+        }
+        lines += 1;
+        let (Some(label), _) = caption_speaker(line) else {
+            continue;
+        };
+        let normalized = normalize_name(&label);
+        if label.chars().count() < 2
+            || !label.chars().next().is_some_and(|c| c.is_uppercase() || !c.is_ascii())
+            || NON_SPEAKER_LABELS.contains(&normalized.as_str())
+            || (names_known(projects, &label) && !names_known(assignees, &label))
+        {
+            continue;
+        }
+        turns += 1;
+        if !labels.iter().any(|l| normalize_name(l) == normalized) {
+            labels.push(label);
+        }
+    }
+    if turns < 2 || turns * 2 < lines {
+        return Vec::new();
+    }
+    labels
+        .into_iter()
+        .take(50)
+        .map(|label| {
+            let (assignee, method, candidates) = match resolve_name(assignees, &label) {
+                ResolvedName::Exact => (Some(label.clone()), None, Vec::new()),
+                ResolvedName::Matched(name, how) => (Some(name), Some(how), Vec::new()),
+                ResolvedName::Ambiguous(candidates) => (None, None, candidates),
+                ResolvedName::Unresolved => (None, None, Vec::new()),
+            };
+            Speaker {
+                label,
+                assignee,
+                method,
+                candidates,
+            }
+        })
+        .collect()
+}
+
+/// Instructions appended to the extraction prompt when the notes are a transcript, mapping
+/// each speaker to their assignee.
+fn speaker_instructions(speakers: &[Speaker]) -> String {
+    let mut out = String::from(
+        "\nSpeakers:\n- The notes are a transcript: lines start with the name of the person speaking.\n\
+         - When a speaker takes a task on themselves (\"I'll handle the migration\", \"I can look into it\"), \
+         set \"speaker\" to their name as written in the notes; otherwise set it to null.\n",
+    );
+    let matched: Vec<String> = speakers
+        .iter()
+        .filter_map(|s| s.assignee.as_ref().map(|a| format!("{} is {}", s.label, a)))
+        .collect();
+    if !matched.is_empty() {
+        out.push_str(&format!(
+            "- Assign the tasks these speakers take on to them: {}.\n",
+            matched.join("; ")
+        ));
+    }
+    let unmatched: Vec<&str> = speakers
+        .iter()
+        .filter(|s| s.assignee.is_none())
+        .map(|s| s.label.as_str())
+        .collect();
+    if !unmatched.is_empty() {
+        out.push_str(&format!(
+            "- These speakers are not existing assignees; leave the tasks they take on \"Unassigned\": {}.\n",
+            unmatched.join(", ")
+        ));
+    }
+    out
+}
+
+/// Gives a task the model left unassigned to the assignee matched to `returned`, the speaker
+/// who took it on, and reports speakers who matched no assignee.
+fn assign_to_speaker(
+    names: &mut Vec<NameResolution>,
+    speakers: &[Speaker],
+    returned: &str,
+    assignee: &mut String,
+    title: &str,
+) {
+    let wanted = normalize_name(returned);
+    let Some(speaker) = speakers.iter().find(|s| normalize_name(&s.label) == wanted) else {
+        return;
+    };
+    match &speaker.assignee {
+        Some(name) if assignee == "Unassigned" => {
+            *assignee = name.clone();
+            record_name_resolution(
+                names,
+                NameResolution {
+                    field: "assignee".to_string(),
+                    returned: speaker.label.clone(),
+                    used: name.clone(),
+                    status: NameStatus::Speaker,
+                    method: speaker.method,
+                    candidates: Vec::new(),
+                    confidence: None,
+                    tasks: vec![title.to_string()],
+                },
+            );
+        }
+        Some(_) => {}
+        None => record_name_resolution(names, speaker.unmatched(vec![title.to_string()])),
+    }
+}
+
+/// The extraction system prompt rendered with the current projects, assignees and date,
+/// along with those names for resolving the model's answers.
+struct ExtractionPrompt {
+    version: i64,
+    system: String,
+    projects: Vec<KnownName>,
+    assignees: Vec<KnownName>,
+    /// Relative deadlines in the notes are resolved against this date.
+    meeting_date: NaiveDate,
+    deadlines: DeadlineConfig,
+    language: ExtractionLanguage,
+    /// Speakers of transcript-style notes; empty for other notes.
+    speakers: Vec<Speaker>,
+}
+
+/// Existing projects, with their keys as aliases, and assignees with their aliases.
+async fn load_known_names(pool: &SqlitePool) -> Result<(Vec<KnownName>, Vec<KnownName>), sqlx::Error> {
+    let projects: Vec<KnownName> = sqlx::query_as::<_, (String, Option<String>)>("SELECT name, key FROM projects ORDER BY name")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|(name, key)| KnownName {
+            name,
+            aliases: key.into_iter().collect(),
+        })
+        .collect();
+    let assignees: Vec<KnownName> = sqlx::query_as::<_, Assignee>("SELECT id, name, aliases FROM assignees ORDER BY name")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|a| KnownName {
+            name: a.name,
+            aliases: a.aliases,
+        })
+        .collect();
+    Ok((projects, assignees))
+}
+
+fn describe_names(names: &[KnownName]) -> String {
+    names.iter().map(KnownName::describe).collect::<Vec<_>>().join(", ")
+}
+
+async fn load_extraction_prompt(
+    pool: &SqlitePool,
+    deadlines: &DeadlineConfig,
+    meeting_date: NaiveDate,
+    language: ExtractionLanguage,
+    notes: &str,
+) -> Result<ExtractionPrompt, sqlx::Error> {
+    let template = latest_prompt(pool, EXTRACTION_PROMPT)
+        .await?
+        .ok_or_else(|| sqlx::Error::RowNotFound)?;
+    let (projects, assignees) = load_known_names(pool).await?;
+    let mut system = render_prompt(
+        &template.template,
+        &[
+            ("today", deadlines.today().format("%Y-%m-%d").to_string()),
+            ("meeting_date", meeting_date.format("%Y-%m-%d").to_string()),
+            ("projects", describe_names(&projects)),
+            ("assignees", describe_names(&assignees)),
+            ("conventions", template.conventions),
+        ],
+    );
+    system.push_str(&language.instructions());
+    let speakers = transcript_speakers(notes, &projects, &assignees);
+    if !speakers.is_empty() {
+        system.push_str(&speaker_instructions(&speakers));
+    }
+    Ok(ExtractionPrompt {
+        version: template.version,
+        system,
+        projects,
+        assignees,
+        meeting_date,
+        deadlines: *deadlines,
+        language,
+        speakers,
+    })
+}
+
+/// Asks for JSON matching `schema` and parses the answer with `parse`. An answer that cannot
+/// be parsed is sent back to the model once with the error; `what` names the expected
+/// content in the error when the second answer fails too.
+async fn complete_parsed<T>(
+    ai: &dyn AiProvider,
+    mut messages: Vec<ChatMessage>,
+    schema_name: &str,
+    schema: &serde_json::Value,
+    what: &str,
+    parse: impl Fn(&str) -> std::result::Result<T, String>,
+) -> Result<(T, Completion), AiError> {
+    let completion = ai.complete_json(&messages, schema_name, schema).await?;
+    let err = match parse(&completion.content) {
+        Ok(value) => return Ok((value, completion)),
+        Err(err) => err,
+    };
+    messages.push(ChatMessage::assistant(completion.content));
+    messages.push(ChatMessage::user(format!(
+        "Your previous response could not be parsed: {}. Reply again with only the JSON object described above.",
+        err
+    )));
+    let completion = ai.complete_json(&messages, schema_name, schema).await?;
+    match parse(&completion.content) {
+        Ok(value) => Ok((value, completion)),
+        Err(err) => Err(AiError::InvalidResponse(format!(
+            "{} response could not be parsed as {}: {}",
+            ai.name(),
+            what,
+            err
+        ))),
+    }
+}
+
+/// Asks the provider for tasks in `notes` and maps them onto existing projects and assignees.
+/// A response that cannot be parsed is sent back to the model once with the parse error.
+/// Nothing is written to the database.
+async fn propose_tasks(
+    ai: &dyn AiProvider,
+    prompt: &ExtractionPrompt,
+    notes: &str,
+    part: Option<(usize, usize)>,
+) -> Result<std::result::Result<ProposedTasks, AiError>> {
+
+    let schema = task_extraction_schema(prompt.language.keep_original, !prompt.speakers.is_empty());
+    let user_prompt = match part {
+        Some((index, total)) => format!(
+            "Extract tasks from part {} of {} of these meeting notes. Parts overlap slightly; \
+             extract every task mentioned in this part:\n\n{}",
+            index + 1,
+            total,
+            notes
+        ),
+        None => format!("Extract tasks from these meeting notes:\n\n{}", notes),
+    };
+    let messages = vec![ChatMessage::system(prompt.system.clone()), ChatMessage::user(user_prompt)];
+    let items = match complete_parsed(ai, messages, "tasks", &schema, "tasks", parse_extraction).await {
+        Ok((items, _)) => items,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let mut proposed = ProposedTasks {
+        tasks: Vec::new(),
+        rejected: Vec::new(),
+        names: Vec::new(),
+        deadlines: Vec::new(),
+    };
+    for (index, item) in items.into_iter().enumerate() {
+        let mut task = match parse_task_item(&item, prompt.meeting_date, &prompt.deadlines) {
+            Ok(task) => task,
+            Err(errors) => {
+                proposed.rejected.push(RejectedItem {
+                    chunk: None,
+                    index,
+                    item,
+                    errors,
+                });
+                continue;
+            }
+        };
+        let title = task.title.clone();
+        if let Some(returned) = item.get("deadline").and_then(|d| d.as_str()).map(str::trim) {
+            if !returned.is_empty() && !is_iso_date(returned) {
+                record_deadline_resolution(
+                    &mut proposed.deadlines,
+                    DeadlineResolution {
+                        returned: returned.to_string(),
+                        resolved: task.deadline.clone(),
+                        tasks: vec![title.clone()],
+                    },
+                );
+            }
+        }
+        if prompt.language.keep_original {
+            if let Some(source_text) = item.get("source_text").and_then(|s| s.as_str()).filter(|s| !s.trim().is_empty()) {
+                task.notes = Some(prompt.language.original_note(source_text));
+            }
+        }
+        resolve_task_name(&mut proposed.names, &prompt.projects, "project", "General", &mut task.project, &title);
+        resolve_task_name(&mut proposed.names, &prompt.assignees, "assignee", "Unassigned", &mut task.assignee, &title);
+        if let Some(speaker) = item.get("speaker").and_then(|s| s.as_str()) {
+            assign_to_speaker(&mut proposed.names, &prompt.speakers, speaker, &mut task.assignee, &title);
+        }
+        task.prompt_version = Some(prompt.version);
+        proposed.tasks.push(task);
+    }
+    Ok(Ok(proposed))
+}
+
+#[derive(Serialize)]
+struct TaskDraft {
+    draft_id: i64,
+    meeting_id: Option<i64>,
+    tasks: Vec<TaskOutcome>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rejected: Vec<RejectedItem>,
+    /// Project and assignee names that were remapped or could not be resolved, and transcript
+    /// speakers who matched no assignee.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    names: Vec<NameResolution>,
+    /// Relative deadlines and the dates they were resolved to.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    deadlines: Vec<DeadlineResolution>,
+}
+
+#[derive(Serialize)]
+struct GeneratedTasks {
+    meeting_id: i64,
+    tasks: Vec<TaskOutcome>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rejected: Vec<RejectedItem>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    names: Vec<NameResolution>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    deadlines: Vec<DeadlineResolution>,
+}
+
+/// Chunking and concurrency settings for extraction, read from the environment at startup.
+struct ExtractionConfig {
+    /// `AI_CHUNK_TOKENS` (default 3000): estimated size of each chunk of notes.
+    chunk_tokens: usize,
+    /// `AI_CHUNK_OVERLAP_TOKENS` (default 200): how much of the previous chunk is repeated.
+    overlap_tokens: usize,
+    /// `AI_CONCURRENCY` (default 3): chunks extracted at the same time.
+    concurrency: usize,
+    /// `AUTO_ASSIGN_MIN_CONFIDENCE` (default 0.6): unassigned extracted tasks get the assignee
+    /// suggested by their history when the suggestion is at least this confident.
+    assign_min_confidence: f64,
+    /// `AI_OUTPUT_LANGUAGE`: language tasks are written in when the request names none;
+    /// unset keeps the language of the notes.
+    output_language: Option<&'static str>,
+}
+
+impl ExtractionConfig {
+    fn from_env() -> Self {
+        let var = |name: &str, default: usize| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(default)
+        };
+        let chunk_tokens = var("AI_CHUNK_TOKENS", 3000);
+        ExtractionConfig {
+            chunk_tokens,
+            overlap_tokens: var("AI_CHUNK_OVERLAP_TOKENS", 200).min(chunk_tokens / 2),
+            concurrency: var("AI_CONCURRENCY", 3),
+            assign_min_confidence: std::env::var("AUTO_ASSIGN_MIN_CONFIDENCE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.6),
+            output_language: std::env::var("AI_OUTPUT_LANGUAGE").ok().and_then(|v| language_code(&v)),
+        }
+    }
+}
+
+/// Splits `notes` on line boundaries into chunks of about `max_tokens` (estimated at four
+/// characters per token). Each chunk after the first starts with the last `overlap_tokens`
+/// of the previous one so tasks spanning a boundary are not lost.
+fn chunk_notes(notes: &str, max_tokens: usize, overlap_tokens: usize) -> Vec<String> {
+    let max_chars = max_tokens.max(1) * 4;
+    let overlap_chars = overlap_tokens * 4;
+
+    let mut lines: Vec<&str> = Vec::new();
+    for line in notes.lines() {
+        let mut rest = line;
+        while rest.len() > max_chars {
+            let mut split = max_chars;
+            while !rest.is_char_boundary(split) {
+                split -= 1;
+            }
+            let (head, tail) = rest.split_at(split);
+            lines.push(head);
+            rest = tail;
+        }
+        lines.push(rest);
+    }
+
+    let mut chunks = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut current_len = 0;
+    for line in lines {
+        if current_len + line.len() + 1 > max_chars && !current.is_empty() {
+            chunks.push(current.join("\n"));
+            let mut overlap: Vec<&str> = Vec::new();
+            let mut overlap_len = 0;
+            for prev in current.iter().rev() {
+                if overlap_len + prev.len() + 1 > overlap_chars || overlap_len + prev.len() + line.len() + 2 > max_chars {
+                    break;
+                }
+                overlap_len += prev.len() + 1;
+                overlap.push(prev);
+            }
+            overlap.reverse();
+            current = overlap;
+            current_len = overlap_len;
+        }
+        current_len += line.len() + 1;
+        current.push(line);
+    }
+    if current.iter().any(|l| !l.trim().is_empty()) {
+        chunks.push(current.join("\n"));
+    }
+    chunks
+}
+
+/// Adds the tasks extracted from chunk `chunk` to `extracted` (tasks tagged with their chunk).
+/// Chunks overlap, so an item near a boundary is often extracted twice; each task is folded
+/// into the most similar task of an earlier chunk instead. Tasks of the same chunk are never
+/// merged, and an earlier task absorbs at most one task of each chunk, so similar but distinct
+/// tasks the model returned separately are kept.
+fn merge_chunk_tasks(extracted: &mut Vec<(usize, Task)>, chunk: usize, tasks: Vec<Task>) {
+    let mut absorbed: HashSet<usize> = HashSet::new();
+    for task in tasks {
+        let best = extracted
+            .iter()
+            .enumerate()
+            .filter(|(i, (from, _))| *from != chunk && !absorbed.contains(i))
+            .map(|(i, (_, existing))| (i, lexical_similarity(existing, &task)))
+            .filter(|(_, score)| *score >= DUPLICATE_THRESHOLD)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match best {
+            Some((i, _)) => {
+                merge_duplicate(&mut extracted[i].1, &task);
+                absorbed.insert(i);
+            }
+            None => extracted.push((chunk, task)),
+        }
+    }
+}
+
+/// Work submitted to the background queue, stored as JSON in `jobs.payload` so queued and
+/// interrupted jobs survive a restart.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JobPayload {
+    /// Task extraction from new notes (`notes`) or from a stored meeting (`meeting_id`).
+    ExtractTasks {
+        #[serde(default)]
+        notes: Option<GenerateTasksRequest>,
+        #[serde(default)]
+        meeting_id: Option<i64>,
+        query: GenerateTasksQuery,
+        #[serde(default)]
+        provider: Option<String>,
+        #[serde(default)]
+        model: Option<String>,
+        /// Who asked, from the `X-User` header; usage is accounted to them.
+        #[serde(default)]
+        user: Option<String>,
+        #[serde(default)]
+        language: LanguageOptions,
+    },
+}
+
+impl JobPayload {
+    fn kind(&self) -> &'static str {
+        match self {
+            JobPayload::ExtractTasks { .. } => "extract_tasks",
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct JobRow {
+    id: i64,
+    kind: String,
+    status: String,
+    attempts: i64,
+    max_attempts: i64,
+    completed: i64,
+    total: i64,
+    result: Option<String>,
+    error: Option<String>,
+    run_after: String,
+    created_at: String,
+    updated_at: String,
+}
+
+/// A queued, running or finished background job. `status` is `queued`, `running`,
+/// `succeeded`, `failed` or `cancelled`; `completed`/`total` report progress.
+#[derive(Serialize)]
+struct Job {
+    id: i64,
+    kind: String,
+    status: String,
+    attempts: i64,
+    max_attempts: i64,
+    completed: i64,
+    total: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Earliest time a queued job (e.g. one waiting to be retried) will start.
+    run_after: String,
+    created_at: String,
+    updated_at: String,
+}
+
+impl JobRow {
+    fn into_job(self) -> Result<Job, serde_json::Error> {
+        Ok(Job {
+            id: self.id,
+            kind: self.kind,
+            status: self.status,
+            attempts: self.attempts,
+            max_attempts: self.max_attempts,
+            completed: self.completed,
+            total: self.total,
+            result: self.result.map(|r| serde_json::from_str(&r)).transpose()?,
+            error: self.error,
+            run_after: self.run_after,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        })
+    }
+}
+
+async fn fetch_job(pool: &SqlitePool, id: i64) -> Result<Option<Job>> {
+    let row = sqlx::query_as::<_, JobRow>(
+        "SELECT id, kind, status, attempts, max_attempts, completed, total, result, error, run_after, created_at, updated_at FROM jobs WHERE id=?",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    row.map(|r| r.into_job())
+        .transpose()
+        .map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Queues `payload` and answers `202 Accepted` with the job to poll at `GET /jobs/{id}`.
+async fn enqueue_job(data: &AppState, payload: &JobPayload) -> Result<HttpResponse> {
+    let payload_json = serde_json::to_string(payload).map_err(actix_web::error::ErrorInternalServerError)?;
+    let id = sqlx::query_scalar::<_, i64>("INSERT INTO jobs (kind, payload, max_attempts) VALUES (?, ?, ?) RETURNING id")
+        .bind(payload.kind())
+        .bind(&payload_json)
+        .bind(data.job_max_attempts)
+        .fetch_one(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    data.job_notify.notify_one();
+    let job = fetch_job(&data.pool, id)
+        .await?
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("job disappeared"))?;
+    Ok(HttpResponse::Accepted()
+        .insert_header(("Location", format!("/jobs/{}", id)))
+        .json(job))
+}
+
+async fn get_job(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse> {
+    match fetch_job(&data.pool, path.into_inner()).await? {
+        Some(job) => Ok(HttpResponse::Ok().json(job)),
+        None => Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "job not found" }))),
+    }
+}
+
+/// Cancels a queued or running job. A running job stops at its next check (within a second)
+/// and nothing it had not yet saved is written.
+async fn cancel_job(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let result = sqlx::query(
+        "UPDATE jobs SET status='cancelled', updated_at=CURRENT_TIMESTAMP WHERE id=? AND status IN ('queued', 'running')",
+    )
+    .bind(id)
+    .execute(&data.pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(job) = fetch_job(&data.pool, id).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "job not found" })));
+    };
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::Conflict().json(serde_json::json!({
+            "error": format!("job is already {}", job.status)
+        })));
+    }
+    Ok(HttpResponse::Ok().json(job))
+}
+
+async fn set_job_progress(pool: &SqlitePool, id: i64, completed: usize, total: usize) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE jobs SET completed=?, total=?, updated_at=CURRENT_TIMESTAMP WHERE id=?")
+        .bind(completed as i64)
+        .bind(total as i64)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+enum JobFailure {
+    /// Worth trying again later: network errors, timeouts, rate limits and 5xx answers.
+    Retryable(String),
+    Fatal(String),
+}
+
+impl From<AiError> for JobFailure {
+    fn from(e: AiError) -> Self {
+        match e {
+            AiError::Request(msg) | AiError::Unavailable(msg) => JobFailure::Retryable(msg),
+            other => JobFailure::Fatal(other.message().to_string()),
+        }
+    }
+}
+
+async fn execute_job(
+    data: &web::Data<AppState>,
+    id: i64,
+    payload: JobPayload,
+) -> std::result::Result<serde_json::Value, JobFailure> {
+    match payload {
+        JobPayload::ExtractTasks {
+            notes,
+            meeting_id,
+            query,
+            provider,
+            model,
+            user,
+            language,
+        } => {
+            if let Some(msg) = budget_exceeded(data).await.map_err(|e| JobFailure::Retryable(e.to_string()))? {
+                return Err(JobFailure::Fatal(msg));
+            }
+            let ai = match (&provider, &model) {
+                (None, None) => data.ai.clone(),
+                _ => {
+                    let provider = provider
+                        .unwrap_or_else(|| std::env::var("AI_PROVIDER").unwrap_or_else(|_| "gemini".to_string()));
+                    build_ai_provider(&provider, model.as_deref(), &data.http)
+                        .map(Arc::from)
+                        .ok_or_else(|| JobFailure::Fatal(format!("unknown provider '{}'", provider)))?
+                }
+            };
+            let source = match (notes, meeting_id) {
+                (Some(request), _) => MeetingSource::New(request),
+                (None, Some(meeting_id)) => match fetch_meeting(&data.pool, meeting_id).await {
+                    Ok(Some(meeting)) => MeetingSource::Existing(meeting),
+                    Ok(None) => return Err(JobFailure::Fatal("meeting not found".to_string())),
+                    Err(e) => return Err(JobFailure::Retryable(e.to_string())),
+                },
+                (None, None) => return Err(JobFailure::Fatal("job has no notes or meeting".to_string())),
+            };
+            let embedder = duplicate_check_provider(data, user.clone(), Some(id));
+            let ai: Arc<dyn AiProvider> = Arc::new(MeteredProvider {
+                inner: ai,
+                data: data.clone(),
+                operation: "extract_tasks",
+                user,
+                job_id: Some(id),
+            });
+            match run_extraction(data.clone(), ai, &embedder, source, query, &language, Some(id)).await {
+                Ok(Ok(result)) => serde_json::to_value(result).map_err(|e| JobFailure::Fatal(e.to_string())),
+                Ok(Err(e)) => Err(e.into()),
+                Err(e) => Err(JobFailure::Fatal(e.to_string())),
+            }
+        }
+    }
+}
+
+/// Resolves once the job has been cancelled.
+async fn wait_for_cancel(pool: &SqlitePool, id: i64) {
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let status: Option<String> = sqlx::query_scalar("SELECT status FROM jobs WHERE id=?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .unwrap_or(None);
+        if status.as_deref() != Some("running") {
+            return;
+        }
+    }
+}
+
+async fn run_job(data: &web::Data<AppState>, id: i64, payload: &str, attempts: i64, max_attempts: i64) -> Result<(), sqlx::Error> {
+    let outcome = match serde_json::from_str::<JobPayload>(payload) {
+        Ok(payload) => {
+            tokio::select! {
+                outcome = execute_job(data, id, payload) => outcome,
+                _ = wait_for_cancel(&data.pool, id) => return Ok(()),
+            }
+        }
+        Err(e) => Err(JobFailure::Fatal(format!("invalid job payload: {}", e))),
+    };
+
+    match outcome {
+        Ok(result) => {
+            sqlx::query(
+                "UPDATE jobs SET status='succeeded', result=?, error=NULL, updated_at=CURRENT_TIMESTAMP WHERE id=? AND status='running'",
+            )
+            .bind(result.to_string())
+            .bind(id)
+            .execute(&data.pool)
+            .await?;
+        }
+        Err(JobFailure::Retryable(error)) if attempts < max_attempts => {
+            let delay = (5i64 << (attempts - 1).clamp(0, 6)).min(300);
+            sqlx::query(
+                "UPDATE jobs SET status='queued', error=?, run_after=datetime('now', ?), updated_at=CURRENT_TIMESTAMP WHERE id=? AND status='running'",
+            )
+            .bind(&error)
+            .bind(format!("+{} seconds", delay))
+            .bind(id)
+            .execute(&data.pool)
+            .await?;
+        }
+        Err(JobFailure::Retryable(error)) | Err(JobFailure::Fatal(error)) => {
+            sqlx::query("UPDATE jobs SET status='failed', error=?, updated_at=CURRENT_TIMESTAMP WHERE id=? AND status='running'")
+                .bind(&error)
+                .bind(id)
+                .execute(&data.pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Takes the oldest queued job that is due and marks it running.
+async fn claim_job(pool: &SqlitePool) -> Result<Option<(i64, String, i64, i64)>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        UPDATE jobs SET status='running', attempts=attempts+1, updated_at=CURRENT_TIMESTAMP
+        WHERE id = (
+            SELECT id FROM jobs WHERE status='queued' AND run_after <= datetime('now') ORDER BY id LIMIT 1
+        )
+        RETURNING id, payload, attempts, max_attempts
+        "#,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// One of the `JOB_WORKERS` loops processing the queue.
+async fn job_worker(data: web::Data<AppState>) {
+    loop {
+        match claim_job(&data.pool).await {
+            Ok(Some((id, payload, attempts, max_attempts))) => {
+                if let Err(e) = run_job(&data, id, &payload, attempts, max_attempts).await {
+                    log::error!("Failed to record result of job {}: {}", id, e);
+                }
+            }
+            Ok(None) => {
+                tokio::select! {
+                    _ = data.job_notify.notified() => {}
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => {}
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to claim job: {}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+/// Requeues jobs interrupted by a restart and drops finished jobs older than 7 days.
+async fn resume_jobs(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE jobs SET status='queued', updated_at=CURRENT_TIMESTAMP WHERE status='running'")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "DELETE FROM jobs WHERE status IN ('succeeded', 'failed', 'cancelled') AND updated_at < datetime('now', '-7 days')",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Where the notes being extracted come from.
+enum MeetingSource {
+    /// Notes posted to `/tasks/generate`; the meeting is stored once extraction succeeds.
+    New(GenerateTasksRequest),
+    Existing(Meeting),
+}
+
+impl MeetingSource {
+    fn notes(&self) -> &str {
+        match self {
+            MeetingSource::New(request) => request.meeting_notes.trim(),
+            MeetingSource::Existing(meeting) => &meeting.notes,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ExtractionResult {
+    Draft(TaskDraft),
+    Created(GeneratedTasks),
+}
+
+/// Extracts tasks from each chunk of the notes (at most `AI_CONCURRENCY` at a time), merges
+/// tasks repeated across chunks, records the meeting and saves the result. Chunk progress is
+/// recorded on the job `job_id` when given. Duplicate checks embed with `embedder`.
+async fn run_extraction(
+    data: web::Data<AppState>,
+    ai: Arc<dyn AiProvider>,
+    embedder: &dyn AiProvider,
+    source: MeetingSource,
+    query: GenerateTasksQuery,
+    language: &LanguageOptions,
+    job_id: Option<i64>,
+) -> Result<std::result::Result<ExtractionResult, AiError>> {
+    let meeting_date = match &source {
+        MeetingSource::New(request) => request.date.as_deref(),
+        MeetingSource::Existing(meeting) => Some(meeting.date.as_str()),
+    }
+    .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+    .unwrap_or_else(|| data.deadlines.today());
+    let stored_language = match &source {
+        MeetingSource::New(_) => None,
+        MeetingSource::Existing(meeting) => meeting.language.as_deref().and_then(language_code),
+    };
+    let language = ExtractionLanguage {
+        notes: language
+            .language
+            .as_deref()
+            .and_then(language_code)
+            .or(stored_language)
+            .or_else(|| detect_language(source.notes())),
+        output: language
+            .output_language
+            .as_deref()
+            .and_then(language_code)
+            .or(data.extraction.output_language),
+        keep_original: language.keep_original,
+    };
+    let prompt = load_extraction_prompt(&data.pool, &data.deadlines, meeting_date, language, source.notes())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let chunks = chunk_notes(source.notes(), data.extraction.chunk_tokens, data.extraction.overlap_tokens);
+    let total = chunks.len();
+    if let Some(id) = job_id {
+        set_job_progress(&data.pool, id, 0, total)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+    let completed = AtomicUsize::new(0);
+    let results: Vec<_> = stream::iter(chunks.iter().enumerate())
+        .map(|(index, chunk)| {
+            let data = &data;
+            let ai = ai.as_ref();
+            let completed = &completed;
+            let prompt = &prompt;
+            async move {
+                let part = (total > 1).then_some((index, total));
+                let result = propose_tasks(ai, prompt, chunk, part).await;
+                let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                if let Some(id) = job_id {
+                    set_job_progress(&data.pool, id, done, total)
+                        .await
+                        .map_err(actix_web::error::ErrorInternalServerError)?;
+                }
+                result
+            }
+        })
+        .buffered(data.extraction.concurrency)
+        .collect()
+        .await;
+
+    let mut proposed = ProposedTasks {
+        tasks: Vec::new(),
+        rejected: Vec::new(),
+        names: Vec::new(),
+        deadlines: Vec::new(),
+    };
+    for speaker in prompt.speakers.iter().filter(|s| s.assignee.is_none()) {
+        record_name_resolution(&mut proposed.names, speaker.unmatched(Vec::new()));
+    }
+    let mut extracted: Vec<(usize, Task)> = Vec::new();
+    for (index, result) in results.into_iter().enumerate() {
+        let chunk = match result? {
+            Ok(chunk) => chunk,
+            Err(e) => return Ok(Err(e)),
+        };
+        merge_chunk_tasks(&mut extracted, index, chunk.tasks);
+        proposed.rejected.extend(chunk.rejected.into_iter().map(|r| RejectedItem {
+            chunk: (total > 1).then_some(index),
+            ..r
+        }));
+        for name in chunk.names {
+            record_name_resolution(&mut proposed.names, name);
+        }
+        for deadline in chunk.deadlines {
+            record_deadline_resolution(&mut proposed.deadlines, deadline);
+        }
+    }
+    proposed.tasks = extracted.into_iter().map(|(_, task)| task).collect();
+    suggest_assignees(&data, &mut proposed).await?;
+
+    let (meeting_id, notes) = match source {
+        MeetingSource::New(request) => {
+            let notes = request.meeting_notes.trim();
+            let title = request
+                .title
+                .as_deref()
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| notes.lines().next().unwrap_or("").trim());
+            let title: String = title.chars().take(100).collect();
+            let attendees =
+                serde_json::to_string(&request.attendees).map_err(actix_web::error::ErrorInternalServerError)?;
+            let meeting_id = sqlx::query_scalar::<_, i64>(
+                r#"
+                INSERT INTO meetings (title, date, attendees, notes, provider, model, language)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                RETURNING id
+                "#,
+            )
+            .bind(&title)
+            .bind(meeting_date.format("%Y-%m-%d").to_string())
+            .bind(&attendees)
+            .bind(notes)
+            .bind(ai.name())
+            .bind(ai.model())
+            .bind(language.notes)
+            .fetch_one(&data.pool)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+            (meeting_id, notes.to_string())
+        }
+        MeetingSource::Existing(meeting) => {
+            sqlx::query("UPDATE meetings SET provider=?, model=?, language=? WHERE id=?")
+                .bind(ai.name())
+                .bind(ai.model())
+                .bind(language.notes)
+                .bind(meeting.id)
+                .execute(&data.pool)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+            (meeting.id, meeting.notes)
+        }
+    };
+
+    save_proposed_tasks(&data, embedder, meeting_id, &notes, proposed, &query).await.map(Ok)
+}
+
+/// Prices and monthly caps for provider usage, read from the environment at startup.
+struct AiBudget {
+    /// `AI_PRICES`: USD per million prompt and completion tokens by model, e.g.
+    /// `gemini-2.5-flash=0.30:2.50,grok-3-mini=0.30:0.50`.
+    prices: HashMap<String, (f64, f64)>,
+    /// `AI_MONTHLY_TOKEN_BUDGET`: prompt plus completion tokens per calendar month.
+    monthly_tokens: Option<i64>,
+    /// `AI_MONTHLY_COST_BUDGET`: USD per calendar month; only calls to priced models count.
+    monthly_cost: Option<f64>,
+}
+
+impl AiBudget {
+    fn from_env() -> Self {
+        let prices = std::env::var("AI_PRICES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let (model, price) = entry.split_once('=')?;
+                let (prompt, completion) = price.split_once(':')?;
+                Some((
+                    model.trim().to_string(),
+                    (prompt.trim().parse().ok()?, completion.trim().parse().ok()?),
+                ))
+            })
+            .collect();
+        AiBudget {
+            prices,
+            monthly_tokens: std::env::var("AI_MONTHLY_TOKEN_BUDGET").ok().and_then(|v| v.parse().ok()),
+            monthly_cost: std::env::var("AI_MONTHLY_COST_BUDGET").ok().and_then(|v| v.parse().ok()),
+        }
+    }
+
+    fn cost(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
+        let (prompt, completion) = self.prices.get(model)?;
+        Some((usage.prompt_tokens as f64 * prompt + usage.completion_tokens as f64 * completion) / 1_000_000.0)
+    }
+}
+
+/// The caller named in the `X-User` header, used to account AI usage.
+fn request_user(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get("X-User")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+        .map(|u| u.chars().take(100).collect())
+}
+
+/// Records every completion and embedding request made through it in `ai_usage`, with its
+/// latency and outcome.
+struct MeteredProvider {
+    inner: Arc<dyn AiProvider>,
+    data: web::Data<AppState>,
+    operation: &'static str,
+    user: Option<String>,
+    job_id: Option<i64>,
+}
+
+/// The configured provider, metered as `duplicate_check`, for embedding tasks in duplicate
+/// checks. Always the configured provider so stored embeddings share one model.
+fn duplicate_check_provider(
+    data: &web::Data<AppState>,
+    user: Option<String>,
+    job_id: Option<i64>,
+) -> MeteredProvider {
+    MeteredProvider {
+        inner: data.ai.clone(),
+        data: data.clone(),
+        operation: "duplicate_check",
+        user,
+        job_id,
+    }
+}
+
+impl MeteredProvider {
+    async fn record(&self, started: std::time::Instant, result: &Result<Completion, AiError>) {
+        let (provider, model, usage) = match result {
+            Ok(c) => (c.provider.as_str(), c.model.as_str(), c.usage.unwrap_or_default()),
+            Err(_) => (self.inner.name(), self.inner.model(), TokenUsage::default()),
+        };
+        self.record_call(started, provider, model, usage, result.as_ref().err())
+            .await;
+    }
+
+    async fn record_call(
+        &self,
+        started: std::time::Instant,
+        provider: &str,
+        model: &str,
+        usage: TokenUsage,
+        error: Option<&AiError>,
+    ) {
+        let recorded = sqlx::query(
+            r#"
+            INSERT INTO ai_usage (day, user, operation, provider, model, job_id, prompt_tokens, completion_tokens,
+                                  cost_usd, latency_ms, outcome, error)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(self.data.deadlines.today().format("%Y-%m-%d").to_string())
+        .bind(self.user.as_deref().unwrap_or("anonymous"))
+        .bind(self.operation)
+        .bind(provider)
+        .bind(model)
+        .bind(self.job_id)
+        .bind(usage.prompt_tokens)
+        .bind(usage.completion_tokens)
+        .bind(self.data.budget.cost(model, &usage))
+        .bind(started.elapsed().as_millis() as i64)
+        .bind(if error.is_none() { "ok" } else { "error" })
+        .bind(error.map(AiError::message))
+        .execute(&self.data.pool)
+        .await;
+        if let Err(e) = recorded {
+            log::error!("Failed to record AI usage: {}", e);
+        }
+    }
+}
+
+#[async_trait]
+impl AiProvider for MeteredProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn available(&self) -> bool {
+        self.inner.available()
+    }
+
+    async fn complete(&self, messages: &[ChatMessage]) -> Result<Completion, AiError> {
+        let started = std::time::Instant::now();
+        let result = self.inner.complete(messages).await;
+        self.record(started, &result).await;
+        result
+    }
+
+    async fn complete_json(
+        &self,
+        messages: &[ChatMessage],
+        schema_name: &str,
+        schema: &serde_json::Value,
+    ) -> Result<Completion, AiError> {
+        let started = std::time::Instant::now();
+        let result = self.inner.complete_json(messages, schema_name, schema).await;
+        self.record(started, &result).await;
+        result
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        self.inner.embedding_model()
+    }
+
+    async fn embed(&self, inputs: &[String]) -> Result<Embeddings, AiError> {
+        let started = std::time::Instant::now();
+        let result = self.inner.embed(inputs).await;
+        let model = self.inner.embedding_model().unwrap_or(self.inner.model());
+        let usage = result.as_ref().ok().and_then(|e| e.usage).unwrap_or_default();
+        self.record_call(started, self.inner.name(), model, usage, result.as_ref().err())
+            .await;
+        result
+    }
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct UsageTotals {
+    calls: i64,
+    prompt_tokens: i64,
+    completion_tokens: i64,
+    cost_usd: Option<f64>,
+}
+
+/// Usage since the first day of the current month (in `APP_TIMEZONE`).
+async fn month_usage(data: &AppState) -> Result<UsageTotals, sqlx::Error> {
+    let month_start = data.deadlines.today().with_day(1).unwrap_or_default();
+    sqlx::query_as(
+        "SELECT COUNT(*) AS calls, COALESCE(SUM(prompt_tokens), 0) AS prompt_tokens, \
+         COALESCE(SUM(completion_tokens), 0) AS completion_tokens, SUM(cost_usd) AS cost_usd \
+         FROM ai_usage WHERE day >= ?",
+    )
+    .bind(month_start.format("%Y-%m-%d").to_string())
+    .fetch_one(&data.pool)
+    .await
+}
+
+/// Describes the monthly cap that has been reached, if any.
+async fn budget_exceeded(data: &AppState) -> Result<Option<String>, sqlx::Error> {
+    if data.budget.monthly_tokens.is_none() && data.budget.monthly_cost.is_none() {
+        return Ok(None);
+    }
+    let usage = month_usage(data).await?;
+    let tokens = usage.prompt_tokens + usage.completion_tokens;
+    if let Some(limit) = data.budget.monthly_tokens.filter(|limit| tokens >= *limit) {
+        return Ok(Some(format!(
+            "monthly AI token budget of {} exceeded ({} used)",
+            limit, tokens
+        )));
+    }
+    let cost = usage.cost_usd.unwrap_or(0.0);
+    if let Some(limit) = data.budget.monthly_cost.filter(|limit| cost >= *limit) {
+        return Ok(Some(format!(
+            "monthly AI cost budget of ${:.2} exceeded (${:.2} used)",
+            limit, cost
+        )));
+    }
+    Ok(None)
+}
+
+/// 429 once this month's usage has reached a configured budget.
+async fn ai_over_budget(data: &AppState) -> Result<Option<HttpResponse>> {
+    let exceeded = budget_exceeded(data)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(exceeded.map(|msg| HttpResponse::TooManyRequests().json(serde_json::json!({ "error": msg }))))
+}
+
+#[derive(Deserialize)]
+struct UsageQuery {
+    /// First day to include (default: the first day of the current month).
+    #[serde(default)]
+    from: Option<String>,
+    /// Last day to include (default: today).
+    #[serde(default)]
+    to: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct UsageRow {
+    day: String,
+    user: String,
+    provider: String,
+    model: String,
+    calls: i64,
+    errors: i64,
+    prompt_tokens: i64,
+    completion_tokens: i64,
+    cost_usd: Option<f64>,
+    avg_latency_ms: f64,
+}
+
+/// AI usage per day, user and model, with this month's totals against the budgets.
+async fn get_ai_usage(data: web::Data<AppState>, query: web::Query<UsageQuery>) -> Result<HttpResponse> {
+    let today = data.deadlines.today();
+    let from = query
+        .from
+        .clone()
+        .unwrap_or_else(|| today.with_day(1).unwrap_or(today).format("%Y-%m-%d").to_string());
+    let to = query.to.clone().unwrap_or_else(|| today.format("%Y-%m-%d").to_string());
+    if !is_iso_date(&from) || !is_iso_date(&to) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "from and to must be YYYY-MM-DD" })));
+    }
+    let rows: Vec<UsageRow> = sqlx::query_as(
+        r#"
+        SELECT day, user, provider, model, COUNT(*) AS calls,
+               SUM(CASE WHEN outcome = 'ok' THEN 0 ELSE 1 END) AS errors,
+               SUM(prompt_tokens) AS prompt_tokens, SUM(completion_tokens) AS completion_tokens,
+               SUM(cost_usd) AS cost_usd, AVG(latency_ms) AS avg_latency_ms
+        FROM ai_usage WHERE day >= ? AND day <= ?
+        GROUP BY day, user, provider, model
+        ORDER BY day, user, provider, model
+        "#,
+    )
+    .bind(&from)
+    .bind(&to)
+    .fetch_all(&data.pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    let month = month_usage(&data)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let exceeded = budget_exceeded(&data)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "from": from,
+        "to": to,
+        "usage": rows,
+        "month": {
+            "calls": month.calls,
+            "prompt_tokens": month.prompt_tokens,
+            "completion_tokens": month.completion_tokens,
+            "cost_usd": month.cost_usd,
+            "token_budget": data.budget.monthly_tokens,
+            "cost_budget": data.budget.monthly_cost,
+            "exceeded": exceeded,
+        },
+    })))
+}
+
+/// 503 while the configured provider (and its fallback) is failing fast, so callers are
+/// told right away instead of queueing a job that can only fail.
+fn ai_unavailable(data: &AppState) -> Option<HttpResponse> {
+    if data.ai.available() {
+        return None;
+    }
+    Some(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+        "error": format!("{} is temporarily unavailable after repeated failures; try again later", data.ai.name())
+    })))
+}
+
+/// Queues extraction of tasks from meeting notes; the notes are stored as a meeting the tasks
+/// link back to. With `?dry_run=true` the proposals are saved as a draft for review instead.
+async fn generate_tasks_from_ai(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<GenerateTasksQuery>,
+    body: web::Json<GenerateTasksRequest>,
+) -> Result<HttpResponse> {
+    queue_generation(&req, &data, query.into_inner(), body.into_inner()).await
+}
+
+async fn queue_generation(
+    req: &HttpRequest,
+    data: &web::Data<AppState>,
+    query: GenerateTasksQuery,
+    mut body: GenerateTasksRequest,
+) -> Result<HttpResponse> {
+    if body.meeting_notes.trim().is_empty() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "meeting_notes must not be empty"
+        })));
+    }
+    if let Some(msg) = validate_threshold(query.threshold) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+    if body.date.as_deref().is_some_and(|d| !is_iso_date(d)) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "date must be YYYY-MM-DD" })));
+    }
+    if let Some(msg) = body.language.validate() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+    if let Some(resp) = ai_unavailable(data) {
+        return Ok(resp);
+    }
+    if let Some(resp) = ai_over_budget(data).await? {
+        return Ok(resp);
+    }
+
+    let language = std::mem::take(&mut body.language);
+    let payload = JobPayload::ExtractTasks {
+        notes: Some(body),
+        meeting_id: None,
+        query,
+        provider: None,
+        model: None,
+        user: request_user(req),
+        language,
+    };
+    enqueue_job(data, &payload).await
+}
+
+/// File types `POST /tasks/generate` accepts as uploads.
+const UPLOAD_EXTENSIONS: &[&str] = &["txt", "md", "markdown", "docx", "vtt", "srt", "pdf"];
+
+/// Routes `multipart/form-data` posts to the upload handler.
+fn is_multipart(ctx: &guard::GuardContext) -> bool {
+    ctx.header::<header::ContentType>()
+        .is_some_and(|ct| ct.0.essence_str() == "multipart/form-data")
+}
+
+/// Replaces the entities that occur in captions.
+fn unescape_caption(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Splits a caption into its speaker and text. Speakers come from WebVTT voice tags
+/// (`<v Alice>`), `[Alice]` or a short `Alice:` prefix that is not a label like `Note:`.
+fn caption_speaker(text: &str) -> (Option<String>, String) {
+    let text = text.trim();
+    if let Some(rest) = text.strip_prefix("<v") {
+        if let Some((tag, body)) = rest.split_once('>') {
+            // `<v.loud Alice Smith>`: classes come before the first space.
+            let name = tag.split_once(' ').map(|(_, name)| name.trim()).unwrap_or("");
+            if !name.is_empty() {
+                return (Some(name.to_string()), body.to_string());
+            }
+        }
+    }
+    if let Some(rest) = text.strip_prefix('[') {
+        if let Some((name, body)) = rest.split_once(']') {
+            if !name.trim().is_empty() && name.len() <= 40 {
+                return (Some(name.trim().to_string()), body.to_string());
+            }
+        }
+    }
+    if let Some((name, body)) = text.split_once(':') {
+        let name = name.trim().trim_start_matches(">>").trim();
+        let words = name.split_whitespace().count();
+        let looks_like_name = (1..=4).contains(&words)
+            && name.chars().count() <= 40
+            && !body.starts_with("//")
+            && name.chars().next().is_some_and(|c| c.is_uppercase() || !c.is_ascii())
+            && name.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '.' | '-' | '\'' | '_'))
+            && !NON_SPEAKER_LABELS.contains(&normalize_name(name).as_str());
+        if looks_like_name {
+            return (Some(name.to_string()), body.to_string());
+        }
+    }
+    (None, text.to_string())
+}
+
+/// The spoken text of a WebVTT or SRT file, one line per turn as `Speaker: text`. Timings,
+/// cue numbers, styling and repeated rolling captions are dropped.
+fn caption_text(content: &str) -> String {
+    let content = content.replace("\r\n", "\n").replace('\r', "\n");
+    let mut turns: Vec<(Option<String>, String)> = Vec::new();
+    let mut previous = String::new();
+    for block in content.split("\n\n") {
+        let lines: Vec<&str> = block.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+        let Some(timing) = lines.iter().position(|l| l.contains("-->")) else {
+            continue;
+        };
+        let cue = lines[timing + 1..].join(" ");
+        let (speaker, text) = caption_speaker(&cue);
+        let mut plain = String::new();
+        let mut in_tag = false;
+        for c in text.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' if in_tag => in_tag = false,
+                _ if !in_tag => plain.push(c),
+                _ => {}
+            }
+        }
+        let text = unescape_caption(plain.split_whitespace().collect::<Vec<_>>().join(" ").as_str());
+        if text.is_empty() || text == previous {
+            continue;
+        }
+        previous = text.clone();
+        match (turns.last_mut(), speaker) {
+            (Some((last, line)), speaker) if speaker.is_none() || speaker == *last => {
+                line.push(' ');
+                line.push_str(&text);
+            }
+            (_, speaker) => turns.push((speaker, text)),
+        }
+    }
+    turns
+        .into_iter()
+        .map(|(speaker, text)| match speaker {
+            Some(speaker) => format!("{}: {}", speaker, text),
+            None => text,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The paragraphs of a Word document's body.
+fn docx_text(bytes: &[u8]) -> std::result::Result<String, String> {
+    use quick_xml::events::Event;
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).map_err(|e| format!("not a valid .docx file: {}", e))?;
+    let document = archive
+        .by_name("word/document.xml")
+        .map_err(|_| "not a valid .docx file: word/document.xml is missing".to_string())?;
+    if document.size() > 50 * 1024 * 1024 {
+        return Err("the document is too large".to_string());
+    }
+    let mut xml = String::new();
+    document
+        .take(50 * 1024 * 1024)
+        .read_to_string(&mut xml)
+        .map_err(|e| format!("not a valid .docx file: {}", e))?;
+
+    let mut reader = quick_xml::Reader::from_str(&xml);
+    let mut text = String::new();
+    let mut in_text = false;
+    loop {
+        match reader.read_event().map_err(|e| format!("not a valid .docx file: {}", e))? {
+            Event::Start(e) if e.name().as_ref() == b"w:t" => in_text = true,
+            Event::End(e) => match e.name().as_ref() {
+                b"w:t" => in_text = false,
+                b"w:p" => text.push('\n'),
+                _ => {}
+            },
+            Event::Empty(e) => match e.name().as_ref() {
+                b"w:tab" => text.push('\t'),
+                b"w:br" | b"w:cr" => text.push('\n'),
+                _ => {}
+            },
+            Event::Text(t) if in_text => text.push_str(&t.unescape().map_err(|e| format!("not a valid .docx file: {}", e))?),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(text)
+}
+
+/// Converts an uploaded file to plain text according to its extension.
+fn notes_from_file(filename: &str, bytes: &[u8]) -> std::result::Result<String, String> {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    let utf8 = || String::from_utf8_lossy(bytes).trim_start_matches('\u{feff}').to_string();
+    let text = match extension.as_str() {
+        "txt" | "md" | "markdown" => utf8(),
+        "vtt" | "srt" => caption_text(&utf8()),
+        "docx" => docx_text(bytes)?,
+        // Malformed PDFs can make the parser panic; report them like any unreadable file.
+        "pdf" => std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(bytes))
+            .map_err(|_| "could not read text from the PDF".to_string())?
+            .map_err(|e| format!("could not read text from the PDF: {}", e))?,
+        _ => {
+            return Err(format!(
+                "unsupported file type '{}'; expected one of: .{}",
+                filename,
+                UPLOAD_EXTENSIONS.join(", .")
+            ))
+        }
+    };
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return Err(format!("no text found in '{}'", filename));
+    }
+    Ok(text)
+}
+
+/// Queues extraction from uploaded files: `.txt`, `.md`, `.docx`, `.vtt`/`.srt` captions
+/// (speaker labels kept) and `.pdf`, converted to text here. The other fields of
+/// `GenerateTasksRequest` can be sent as form fields; `title` defaults to the first file name.
+async fn generate_tasks_from_upload(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<GenerateTasksQuery>,
+    mut payload: Multipart,
+) -> Result<HttpResponse> {
+    let max_bytes = std::env::var("MAX_UPLOAD_MB")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(10)
+        * 1024
+        * 1024;
+    let mut request = GenerateTasksRequest {
+        meeting_notes: String::new(),
+        title: None,
+        date: None,
+        attendees: Vec::new(),
+        language: LanguageOptions::default(),
+    };
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut received = 0;
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(actix_web::error::ErrorBadRequest)?;
+        let name = field.name().unwrap_or("").to_string();
+        let filename = field
+            .content_disposition()
+            .and_then(|cd| cd.get_filename())
+            .map(String::from);
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(actix_web::error::ErrorBadRequest)?;
+            received += chunk.len();
+            if received > max_bytes {
+                return Ok(HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                    "error": format!("uploads must be at most {} MB", max_bytes / 1024 / 1024)
+                })));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        if let Some(filename) = filename {
+            files.push((filename, bytes));
+            continue;
+        }
+        let value = String::from_utf8_lossy(&bytes).trim().to_string();
+        match name.as_str() {
+            "meeting_notes" => request.meeting_notes = value,
+            "title" => request.title = Some(value).filter(|v| !v.is_empty()),
+            "date" => request.date = Some(value).filter(|v| !v.is_empty()),
+            "attendees" => request
+                .attendees
+                .extend(value.split(',').map(str::trim).filter(|a| !a.is_empty()).map(String::from)),
+            "language" => request.language.language = Some(value).filter(|v| !v.is_empty()),
+            "output_language" => request.language.output_language = Some(value).filter(|v| !v.is_empty()),
+            "keep_original" => request.language.keep_original = matches!(value.as_str(), "true" | "1" | "on"),
+            _ => {
+                return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("unknown form field '{}'", name)
+                })))
+            }
+        }
+    }
+
+    let mut sections = Vec::new();
+    if !request.meeting_notes.is_empty() {
+        sections.push(std::mem::take(&mut request.meeting_notes));
+    }
+    let several = files.len() + sections.len() > 1;
+    if request.title.is_none() {
+        request.title = files.first().and_then(|(filename, _)| {
+            Path::new(filename).file_stem().and_then(|s| s.to_str()).map(String::from)
+        });
+    }
+    for (filename, bytes) in files {
+        let name = filename.clone();
+        let text = match web::block(move || notes_from_file(&name, &bytes)).await? {
+            Ok(text) => text,
+            Err(msg) => {
+                return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg, "file": filename })));
+            }
+        };
+        sections.push(if several { format!("# {}\n\n{}", filename, text) } else { text });
+    }
+    request.meeting_notes = sections.join("\n\n");
+    queue_generation(&req, &data, query.into_inner(), request).await
+}
+
+/// Links proposals to their meeting and either stores them as a draft or creates them.
+async fn save_proposed_tasks(
+    data: &AppState,
+    embedder: &dyn AiProvider,
+    meeting_id: i64,
+    notes: &str,
+    mut proposed: ProposedTasks,
+    query: &GenerateTasksQuery,
+) -> Result<ExtractionResult> {
+    for task in &mut proposed.tasks {
+        task.meeting_id = Some(meeting_id);
+    }
+
+    if query.dry_run {
+        sqlx::query("DELETE FROM task_drafts WHERE created_at < datetime('now', '-7 days')")
+            .execute(&data.pool)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+        let tasks_json = serde_json::to_string(&proposed.tasks).map_err(actix_web::error::ErrorInternalServerError)?;
+        let rejected_json = serde_json::to_string(&proposed.rejected).map_err(actix_web::error::ErrorInternalServerError)?;
+        let names_json = serde_json::to_string(&proposed.names).map_err(actix_web::error::ErrorInternalServerError)?;
+        let deadlines_json =
+            serde_json::to_string(&proposed.deadlines).map_err(actix_web::error::ErrorInternalServerError)?;
+        let draft_id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO task_drafts (meeting_notes, tasks, rejected, meeting_id, names, deadlines) \
+             VALUES (?, ?, ?, ?, ?, ?) RETURNING id",
+        )
+        .bind(notes)
+        .bind(&tasks_json)
+        .bind(&rejected_json)
+        .bind(meeting_id)
+        .bind(&names_json)
+        .bind(&deadlines_json)
+        .fetch_one(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+        return Ok(ExtractionResult::Draft(TaskDraft {
+            draft_id,
+            meeting_id: Some(meeting_id),
+            tasks: flag_duplicates(data, embedder, proposed.tasks).await?,
+            rejected: proposed.rejected,
+            names: proposed.names,
+            deadlines: proposed.deadlines,
+        }));
+    }
+
+    let mut tasks = Vec::new();
+    let outcomes = insert_checked_tasks(data, embedder, proposed.tasks, query.on_duplicate, query.threshold).await?;
+    for (index, outcome) in outcomes.into_iter().enumerate() {
+        match outcome {
+            Ok(outcome) => tasks.push(outcome),
+            Err((task, reject)) => proposed.rejected.push(RejectedItem {
+                chunk: None,
+                index,
+                item: serde_json::to_value(&task).map_err(actix_web::error::ErrorInternalServerError)?,
+                errors: vec![reject.error],
+            }),
+        }
+    }
+    Ok(ExtractionResult::Created(GeneratedTasks {
+        meeting_id,
+        tasks,
+        rejected: proposed.rejected,
+        names: proposed.names,
+        deadlines: proposed.deadlines,
+    }))
+}
+
+struct StoredDraft {
+    meeting_id: Option<i64>,
+    tasks: Vec<Task>,
+    rejected: Vec<RejectedItem>,
+    names: Vec<NameResolution>,
+    deadlines: Vec<DeadlineResolution>,
+}
+
+async fn load_task_draft(pool: &SqlitePool, draft_id: i64) -> Result<Option<StoredDraft>> {
+    #[derive(sqlx::FromRow)]
+    struct DraftRow {
+        tasks: String,
+        rejected: Option<String>,
+        meeting_id: Option<i64>,
+        names: Option<String>,
+        deadlines: Option<String>,
+    }
+    let row: Option<DraftRow> =
+        sqlx::query_as("SELECT tasks, rejected, meeting_id, names, deadlines FROM task_drafts WHERE id=?")
+        .bind(draft_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(DraftRow {
+        tasks: tasks_json,
+        rejected: rejected_json,
+        meeting_id,
+        names: names_json,
+        deadlines: deadlines_json,
+    }) = row
+    else {
+        return Ok(None);
+    };
+    let tasks = serde_json::from_str(&tasks_json).map_err(actix_web::error::ErrorInternalServerError)?;
+    let rejected = match rejected_json {
+        Some(json) => serde_json::from_str(&json).map_err(actix_web::error::ErrorInternalServerError)?,
+        None => Vec::new(),
+    };
+    let names = match names_json {
+        Some(json) => serde_json::from_str(&json).map_err(actix_web::error::ErrorInternalServerError)?,
+        None => Vec::new(),
+    };
+    let deadlines = match deadlines_json {
+        Some(json) => serde_json::from_str(&json).map_err(actix_web::error::ErrorInternalServerError)?,
+        None => Vec::new(),
+    };
+    Ok(Some(StoredDraft {
+        meeting_id,
+        tasks,
+        rejected,
+        names,
+        deadlines,
+    }))
+}
+
+async fn get_task_draft(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse> {
+    let draft_id = path.into_inner();
+    let ai = duplicate_check_provider(&data, request_user(&req), None);
+    match load_task_draft(&data.pool, draft_id).await? {
+        Some(draft) => Ok(HttpResponse::Ok().json(TaskDraft {
+            draft_id,
+            meeting_id: draft.meeting_id,
+            tasks: flag_duplicates(&data, &ai, draft.tasks).await?,
+            rejected: draft.rejected,
+            names: draft.names,
+            deadlines: draft.deadlines,
+        })),
+        None => Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "draft not found" }))),
+    }
+}
+
+async fn delete_task_draft(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse> {
+    let result = sqlx::query("DELETE FROM task_drafts WHERE id=?")
+        .bind(path.into_inner())
+        .execute(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "draft not found" })));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Parses a body that may be left out: an empty body gives the defaults, but a body that is
+/// sent must be valid JSON for `T`, so a bad field is never silently ignored.
+fn optional_json<T: serde::de::DeserializeOwned + Default>(body: &[u8]) -> std::result::Result<T, String> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body).map_err(|e| format!("invalid request body: {}", e))
+}
+
+#[derive(Deserialize, Default)]
+struct CommitDraftRequest {
+    /// The reviewed tasks to create. Omit to accept the draft unchanged.
+    #[serde(default)]
+    tasks: Option<Vec<Task>>,
+}
+
+/// Creates the accepted (and possibly edited) tasks of a draft in one transaction.
+async fn commit_task_draft(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    query: web::Query<DuplicateQuery>,
+    body: web::Bytes,
+) -> Result<HttpResponse> {
+    let draft_id = path.into_inner();
+    if let Some(msg) = validate_threshold(query.threshold) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+    let body: CommitDraftRequest = match optional_json(&body) {
+        Ok(body) => body,
+        Err(msg) => return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg }))),
+    };
+    let Some(draft) = load_task_draft(&data.pool, draft_id).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "draft not found" })));
+    };
+    let mut tasks = body.tasks.unwrap_or(draft.tasks);
+    for task in &mut tasks {
+        task.meeting_id = draft.meeting_id;
+    }
+
+    for (index, task) in tasks.iter_mut().enumerate() {
+        if let Some(msg) = prepare_new_task(&data, task)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?
+        {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg, "index": index })));
+        }
+    }
+
+    let ai = duplicate_check_provider(&data, request_user(&req), None);
+    let mut index = DuplicateIndex::load(&data, &ai, &tasks).await?;
+    let embeddings = index.embed(&ai, &tasks).await;
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let mut outcomes = Vec::with_capacity(tasks.len());
+    for (i, (task, embedding)) in tasks.into_iter().zip(embeddings).enumerate() {
+        match insert_checked_task(&mut tx, &mut index, task, embedding, query.on_duplicate, query.threshold).await? {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(reject) => {
+                return Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({
+                    "error": reject.error,
+                    "limit": reject.limit,
+                    "current": reject.current,
+                    "index": i,
+                })))
+            }
+        }
+    }
+    let result = sqlx::query("DELETE FROM task_drafts WHERE id=?")
+        .bind(draft_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::Conflict().json(serde_json::json!({ "error": "draft was already committed or discarded" })));
+    }
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Created().json(outcomes))
+}
+
+#[derive(sqlx::FromRow)]
+struct MeetingRow {
+    id: i64,
+    title: String,
+    date: String,
+    attendees: String,
+    notes: String,
+    provider: String,
+    model: String,
+    language: Option<String>,
+    created_at: String,
+}
+
+/// Meeting notes stored as the source of generated tasks. `provider` and `model` are those of
+/// the most recent extraction.
+#[derive(Serialize)]
+struct Meeting {
+    id: i64,
+    title: String,
+    date: String,
+    attendees: Vec<String>,
+    notes: String,
+    provider: String,
+    model: String,
+    /// ISO 639-1 code of the notes, as given or detected at the most recent extraction.
+    language: Option<String>,
+    created_at: String,
+}
+
+impl MeetingRow {
+    fn into_meeting(self) -> Result<Meeting, serde_json::Error> {
+        Ok(Meeting {
+            id: self.id,
+            title: self.title,
+            date: self.date,
+            attendees: serde_json::from_str(&self.attendees)?,
+            notes: self.notes,
+            provider: self.provider,
+            model: self.model,
+            language: self.language,
+            created_at: self.created_at,
+        })
+    }
+}
+
+async fn fetch_meeting(pool: &SqlitePool, id: i64) -> Result<Option<Meeting>> {
+    let row = sqlx::query_as::<_, MeetingRow>(
+        "SELECT id, title, date, attendees, notes, provider, model, language, created_at FROM meetings WHERE id=?",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    row.map(|r| r.into_meeting())
+        .transpose()
+        .map_err(actix_web::error::ErrorInternalServerError)
+}
+
+async fn get_meetings(data: web::Data<AppState>) -> Result<HttpResponse> {
+    let rows = sqlx::query_as::<_, MeetingRow>(
+        "SELECT id, title, date, attendees, notes, provider, model, language, created_at FROM meetings ORDER BY date DESC, id DESC",
+    )
+    .fetch_all(&data.pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    let meetings = rows
+        .into_iter()
+        .map(|r| r.into_meeting())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(meetings))
+}
 
-Example output:
-[{"title":"Review PR #123","description":"Code review for authentication module","tags":["review","urgent"],"deadline":"2025-02-25","project":"Backend","assignee":"Unassigned","status":"todo"}]"#;
+#[derive(Serialize)]
+struct MeetingDetail {
+    #[serde(flatten)]
+    meeting: Meeting,
+    tasks: Vec<Task>,
+}
+
+/// A meeting with its notes and every task extracted from it.
+async fn get_meeting(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse> {
+    let Some(meeting) = fetch_meeting(&data.pool, path.into_inner()).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "meeting not found" })));
+    };
+    let rows = sqlx::query_as::<_, TaskRow>(
+        "SELECT id, title, description, tags, deadline, project, assignee, status, in_sprint, notes, archived, rank, key, meeting_id, prompt_version, parent_id, estimate_hours FROM tasks WHERE meeting_id=? ORDER BY id",
+    )
+    .bind(meeting.id)
+    .fetch_all(&data.pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    let tasks = rows
+        .into_iter()
+        .map(|r| r.into_task())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(MeetingDetail { meeting, tasks }))
+}
+
+#[derive(Deserialize, Default)]
+struct ExtractMeetingRequest {
+    /// `gemini`, `xai` or `openai` (`fake` only with `AI_ALLOW_FAKE_PROVIDER`); defaults to
+    /// the configured provider.
+    #[serde(default)]
+    provider: Option<String>,
+    /// Overrides the provider's configured model.
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(flatten)]
+    language: LanguageOptions,
+}
+
+/// Compares two secrets in time that depends only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// `None` when the request carries `ADMIN_TOKEN` as a bearer token. The admin endpoints are
+/// disabled while no token is configured.
+fn require_admin(req: &HttpRequest) -> Option<HttpResponse> {
+    let Some(token) = std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()) else {
+        return Some(HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "admin endpoints are disabled; set ADMIN_TOKEN to enable them"
+        })));
+    };
+    let authorized = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|v| constant_time_eq(v.as_bytes(), token.as_bytes()));
+    (!authorized).then(|| HttpResponse::Unauthorized().json(serde_json::json!({ "error": "admin token required" })))
+}
+
+/// The latest version of every prompt template.
+async fn list_prompts(req: HttpRequest, data: web::Data<AppState>) -> Result<HttpResponse> {
+    if let Some(resp) = require_admin(&req) {
+        return Ok(resp);
+    }
+    let mut prompts = Vec::new();
+    for (name, _) in PROMPT_TEMPLATES {
+        if let Some(prompt) = latest_prompt(&data.pool, name)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?
+        {
+            prompts.push(prompt);
+        }
+    }
+    Ok(HttpResponse::Ok().json(prompts))
+}
+
+/// Every version of one template, newest first.
+async fn list_prompt_versions(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    if let Some(resp) = require_admin(&req) {
+        return Ok(resp);
+    }
+    let versions: Vec<PromptTemplate> = sqlx::query_as(
+        "SELECT name, version, template, conventions, builtin, created_at FROM prompt_templates \
+         WHERE name=? ORDER BY version DESC",
+    )
+    .bind(path.into_inner())
+    .fetch_all(&data.pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    if versions.is_empty() {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "prompt template not found" })));
+    }
+    Ok(HttpResponse::Ok().json(versions))
+}
+
+#[derive(Deserialize)]
+struct UpdatePromptRequest {
+    template: String,
+    /// Defaults to the conventions of the current version.
+    #[serde(default)]
+    conventions: Option<String>,
+}
 
-    let user_prompt = format!("Extract tasks from these meeting notes:\n\n{}", notes);
+/// Stores a new version of a template; earlier versions are kept so tasks can be traced
+/// back to the prompt that produced them.
+async fn update_prompt(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    body: web::Json<UpdatePromptRequest>,
+) -> Result<HttpResponse> {
+    if let Some(resp) = require_admin(&req) {
+        return Ok(resp);
+    }
+    let name = path.into_inner();
+    let Some(current) = latest_prompt(&data.pool, &name)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "prompt template not found" })));
+    };
+    let body = body.into_inner();
+    if body.template.trim().is_empty() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "template must not be empty" })));
+    }
+    let unknown = unknown_prompt_variables(&body.template);
+    if !unknown.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!(
+                "unknown template variables: {}; available: {}",
+                unknown.join(", "),
+                PROMPT_VARIABLES.join(", ")
+            )
+        })));
+    }
+
+    let prompt: PromptTemplate = sqlx::query_as(
+        "INSERT INTO prompt_templates (name, version, template, conventions) VALUES (?, ?, ?, ?) \
+         RETURNING name, version, template, conventions, builtin, created_at",
+    )
+    .bind(&name)
+    .bind(current.version + 1)
+    .bind(&body.template)
+    .bind(body.conventions.unwrap_or(current.conventions))
+    .fetch_one(&data.pool)
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Created().json(prompt))
+}
 
-    let client = reqwest::Client::new();
-    let chat_req = ChatRequest {
+/// Queues extraction again on a stored meeting, optionally with another provider or model.
+/// Accepts the same query parameters as `POST /tasks/generate`.
+async fn extract_meeting_tasks(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    query: web::Query<GenerateTasksQuery>,
+    body: web::Bytes,
+) -> Result<HttpResponse> {
+    if let Some(msg) = validate_threshold(query.threshold) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+    let Some(meeting) = fetch_meeting(&data.pool, path.into_inner()).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "meeting not found" })));
+    };
+    let body: ExtractMeetingRequest = match optional_json(&body) {
+        Ok(body) => body,
+        Err(msg) => return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg }))),
+    };
+    if let Some(msg) = body.language.validate() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+    let model = body.model.map(|m| m.trim().to_string()).filter(|m| !m.is_empty());
+    if let Some(provider) = &body.provider {
+        // The fake provider invents tasks; callers may only pick it in test setups.
+        let fake_denied = provider.eq_ignore_ascii_case("fake") && !env_flag("AI_ALLOW_FAKE_PROVIDER", false);
+        if fake_denied || build_ai_provider(provider, None, &data.http).is_none() {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "provider must be one of: gemini, xai, openai"
+            })));
+        }
+    } else if let Some(resp) = ai_unavailable(&data) {
+        return Ok(resp);
+    }
+    if let Some(resp) = ai_over_budget(&data).await? {
+        return Ok(resp);
+    }
+    let payload = JobPayload::ExtractTasks {
+        notes: None,
+        meeting_id: Some(meeting.id),
+        query: query.into_inner(),
+        provider: body.provider,
         model,
-        messages: vec![
-            ChatMessage {
-                role: "system".to_string(),
-                content: system_prompt.to_string(),
-            },
-            ChatMessage {
-                role: "user".to_string(),
-                content: user_prompt,
-            },
-        ],
+        user: request_user(&req),
+        language: body.language,
     };
+    enqueue_job(&data, &payload).await
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SummaryRequest {
+    /// Only report on this project.
+    #[serde(default)]
+    project: Option<String>,
+    /// Start of the period for status changes (default: the current sprint's first day when
+    /// `SPRINT_START_DATE` is set, otherwise 13 days before `to`).
+    #[serde(default)]
+    from: Option<String>,
+    /// End of the period (default: today).
+    #[serde(default)]
+    to: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SummaryQuery {
+    /// `markdown` returns the report as `text/markdown` instead of JSON.
+    #[serde(default)]
+    format: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SprintSummary {
+    from: String,
+    to: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    tasks: usize,
+    markdown: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct StatusChangeRow {
+    task_id: i64,
+    from_status: String,
+    to_status: String,
+    comment: Option<String>,
+    changed_at: String,
+}
+
+fn clip(text: &str, max_chars: usize) -> String {
+    let text = text.trim().replace('\n', " ");
+    if text.chars().count() <= max_chars {
+        text
+    } else {
+        format!("{}…", text.chars().take(max_chars).collect::<String>())
+    }
+}
 
-    let provider_name = match provider {
-        AiProvider::Xai => "xAI",
-        AiProvider::Gemini => "Gemini",
+/// Lists the tasks, status changes and deadline risks the summary is written from.
+fn summary_facts(
+    tasks: &[Task],
+    changes: &[StatusChangeRow],
+    from: NaiveDate,
+    to: NaiveDate,
+    project: Option<&str>,
+    today: NaiveDate,
+) -> String {
+    let label = |id: i64| {
+        tasks
+            .iter()
+            .find(|t| t.id == id)
+            .map(|t| match &t.key {
+                Some(key) => format!("[{}] {}", key, t.title),
+                None => t.title.clone(),
+            })
+            .unwrap_or_else(|| format!("task {}", id))
     };
+    let mut out = format!("Reporting period: {} to {}\n", from, to);
+    if let Some(project) = project {
+        out.push_str(&format!("Project: {}\n", project));
+    }
 
-    let resp = client
-        .post(api_url)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&chat_req)
-        .send()
-        .await
-        .map_err(|e| {
-            actix_web::error::ErrorInternalServerError(format!(
-                "{} API request failed: {}",
-                provider_name, e
-            ))
-        })?;
+    out.push_str("\nTasks:\n");
+    for task in tasks {
+        out.push_str(&format!(
+            "- {} (status: {}, assignee: {}, project: {}, deadline: {}{})\n",
+            label(task.id),
+            task.status,
+            task.assignee,
+            task.project,
+            task.deadline.as_deref().unwrap_or("none"),
+            if task.in_sprint { ", in sprint" } else { "" }
+        ));
+        if !task.description.trim().is_empty() {
+            out.push_str(&format!("  Description: {}\n", clip(&task.description, 300)));
+        }
+        if let Some(notes) = &task.notes {
+            out.push_str(&format!("  Notes: {}\n", clip(notes, 300)));
+        }
+    }
+
+    if !changes.is_empty() {
+        out.push_str("\nStatus changes:\n");
+        for change in changes {
+            out.push_str(&format!(
+                "- {} {}: {} -> {}",
+                &change.changed_at[..change.changed_at.len().min(10)],
+                label(change.task_id),
+                change.from_status,
+                change.to_status
+            ));
+            if let Some(comment) = change.comment.as_deref().filter(|c| !c.trim().is_empty()) {
+                out.push_str(&format!(" (\"{}\")", clip(comment, 200)));
+            }
+            out.push('\n');
+        }
+    }
+
+    let soon = today + Days::new(7);
+    let mut at_risk: Vec<(&Task, NaiveDate)> = tasks
+        .iter()
+        .filter(|t| t.status != "done")
+        .filter_map(|t| Some((t, NaiveDate::parse_from_str(t.deadline.as_deref()?, "%Y-%m-%d").ok()?)))
+        .filter(|(_, deadline)| *deadline <= soon)
+        .collect();
+    at_risk.sort_by_key(|(_, deadline)| *deadline);
+    if !at_risk.is_empty() {
+        out.push_str("\nDeadlines due within a week or overdue (not done):\n");
+        for (task, deadline) in at_risk {
+            let days = (deadline - today).num_days();
+            let when = match days {
+                d if d < 0 => format!("overdue by {} days", -d),
+                0 => "due today".to_string(),
+                d => format!("due in {} days", d),
+            };
+            out.push_str(&format!("- {}: {} ({})\n", label(task.id), deadline, when));
+        }
+    }
+    out
+}
 
-    let status = resp.status();
-    let body_text = resp.text().await.map_err(actix_web::error::ErrorInternalServerError)?;
+/// Drops a Markdown code fence the model may have wrapped the report in.
+fn strip_markdown_fence(text: &str) -> String {
+    let text = text.trim();
+    let Some(rest) = text.strip_prefix("```") else {
+        return text.to_string();
+    };
+    let rest = rest.split_once('\n').map(|(_, body)| body).unwrap_or("");
+    rest.trim_end().strip_suffix("```").unwrap_or(rest).trim().to_string()
+}
 
-    let chat_resp: ChatResponse = serde_json::from_str(&body_text).unwrap_or(ChatResponse {
-        choices: None,
-        error: Some(ChatError {
-            message: body_text.clone(),
-        }),
+/// Writes a progress report for the tasks in the current sprint plus any task whose status
+/// changed in the period, optionally limited to one project. `?format=markdown` returns
+/// the report alone, ready to paste into an update email.
+async fn summarize_current_sprint(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<SummaryQuery>,
+    body: web::Json<SummaryRequest>,
+) -> Result<HttpResponse> {
+    let body = body.into_inner();
+    let today = data.deadlines.today();
+    let parse = |value: &Option<String>| match value.as_deref() {
+        None => Ok(None),
+        Some(d) if is_iso_date(d) => Ok(NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()),
+        Some(_) => Err(()),
+    };
+    let (Ok(from), Ok(to)) = (parse(&body.from), parse(&body.to)) else {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "from and to must be YYYY-MM-DD" })));
+    };
+    let to = to.unwrap_or(today);
+    let from = from.unwrap_or_else(|| {
+        data.deadlines
+            .sprint_end(to, 0)
+            .and_then(|end| end.checked_sub_days(Days::new(data.deadlines.sprint_length_days - 1)))
+            .unwrap_or(to - Days::new(13))
     });
+    if from > to {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "from must not be after to" })));
+    }
+    let project = body.project.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(project) = &project {
+        if !project_exists(&data.pool, project)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?
+        {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "project does not exist" })));
+        }
+    }
 
-    if !status.is_success() {
-        let err_msg = chat_resp
-            .error
-            .map(|e| e.message)
-            .unwrap_or_else(|| format!("{} API error: {} - {}", provider_name, status, body_text));
-        return Ok(HttpResponse::BadGateway().json(serde_json::json!({
-            "error": err_msg
-        })));
+    let (from_str, to_str) = (from.format("%Y-%m-%d").to_string(), to.format("%Y-%m-%d").to_string());
+    let mut qb = QueryBuilder::<Sqlite>::new(
+        "SELECT id, title, description, tags, deadline, project, assignee, status, in_sprint, notes, archived, rank, key, meeting_id, prompt_version, parent_id, estimate_hours FROM tasks \
+         WHERE archived = 0 AND (in_sprint = 1 OR id IN (SELECT task_id FROM task_status_changes WHERE date(changed_at) BETWEEN ",
+    );
+    qb.push_bind(&from_str).push(" AND ").push_bind(&to_str).push("))");
+    if let Some(project) = &project {
+        qb.push(" AND project = ").push_bind(project);
     }
+    qb.push(" ORDER BY project, rank");
+    let tasks = qb
+        .build_query_as::<TaskRow>()
+        .fetch_all(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .into_iter()
+        .map(TaskRow::into_task)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(actix_web::error::ErrorInternalServerError)?;
 
-    let content = chat_resp
-        .choices
-        .and_then(|c| c.into_iter().next())
-        .and_then(|c| c.message.content)
-        .ok_or_else(|| {
-            actix_web::error::ErrorInternalServerError(format!(
-                "No content in {} response",
-                provider_name
-            ))
-        })?;
+    let summary = if tasks.is_empty() {
+        SprintSummary {
+            from: from_str,
+            to: to_str,
+            project,
+            tasks: 0,
+            markdown: "No tasks in the current sprint and no status changes in this period.".to_string(),
+            provider: None,
+            model: None,
+        }
+    } else {
+        if let Some(resp) = ai_unavailable(&data) {
+            return Ok(resp);
+        }
+        if let Some(resp) = ai_over_budget(&data).await? {
+            return Ok(resp);
+        }
+        let ids: HashSet<i64> = tasks.iter().map(|t| t.id).collect();
+        let changes: Vec<StatusChangeRow> = sqlx::query_as(
+            "SELECT task_id, from_status, to_status, comment, changed_at FROM task_status_changes \
+             WHERE date(changed_at) BETWEEN ? AND ? ORDER BY changed_at, id",
+        )
+        .bind(&from_str)
+        .bind(&to_str)
+        .fetch_all(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .into_iter()
+        .filter(|c: &StatusChangeRow| ids.contains(&c.task_id))
+        .collect();
 
-    let json_str = extract_json_from_response(&content).unwrap_or(content.as_str());
-    let generated: Vec<serde_json::Value> = serde_json::from_str(json_str).map_err(|e| {
-        actix_web::error::ErrorInternalServerError(format!(
-            "Failed to parse AI response as JSON: {}. Raw: {}",
-            e, json_str
-        ))
-    })?;
-
-    let mut created = Vec::new();
-    for item in generated {
-        let title = item
-            .get("title")
-            .and_then(|v| v.as_str())
-            .unwrap_or("Untitled")
-            .to_string();
-        let description = item
-            .get("description")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
-        let tags: Vec<String> = item
-            .get("tags")
-            .and_then(|v| v.as_array())
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|v| v.as_str().map(String::from))
-                    .collect()
-            })
-            .unwrap_or_else(|| vec!["ai-generated".to_string()]);
-        let deadline = item
-            .get("deadline")
-            .and_then(|v| v.as_str())
-            .filter(|s| !s.is_empty())
-            .map(String::from);
-        let project_raw = item
-            .get("project")
-            .and_then(|v| v.as_str())
-            .unwrap_or("General")
-            .to_string();
-        let assignee_raw = item
-            .get("assignee")
-            .and_then(|v| v.as_str())
-            .unwrap_or("Unassigned")
-            .to_string();
-        let project = if project_exists(&data.pool, &project_raw).await.unwrap_or(false) {
-            project_raw.clone()
-        } else {
-            "General".to_string()
+        let template = latest_prompt(&data.pool, SUMMARY_PROMPT)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?
+            .ok_or_else(|| actix_web::error::ErrorInternalServerError("summary prompt is missing"))?;
+        let system = render_prompt(
+            &template.template,
+            &[
+                ("today", today.format("%Y-%m-%d").to_string()),
+                ("conventions", template.conventions),
+            ],
+        );
+        let facts = summary_facts(&tasks, &changes, from, to, project.as_deref(), today);
+        let ai = MeteredProvider {
+            inner: data.ai.clone(),
+            data: data.clone(),
+            operation: "sprint_summary",
+            user: request_user(&req),
+            job_id: None,
         };
-        let assignee = if assignee_exists(&data.pool, &assignee_raw).await.unwrap_or(false) {
-            assignee_raw.clone()
-        } else {
-            "Unassigned".to_string()
+        let completion = match ai.complete(&[ChatMessage::system(system), ChatMessage::user(facts)]).await {
+            Ok(completion) => completion,
+            Err(e) => return Ok(e.into_response()),
+        };
+        SprintSummary {
+            from: from_str,
+            to: to_str,
+            project,
+            tasks: tasks.len(),
+            markdown: strip_markdown_fence(&completion.content),
+            provider: Some(completion.provider),
+            model: Some(completion.model),
+        }
+    };
+
+    if query.format.as_deref() == Some("markdown") {
+        return Ok(HttpResponse::Ok()
+            .content_type("text/markdown; charset=utf-8")
+            .body(summary.markdown));
+    }
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+#[derive(Deserialize)]
+struct AskRequest {
+    question: String,
+}
+
+#[derive(Serialize)]
+struct AskResponse {
+    question: String,
+    /// The filter the question was translated into, as accepted by `GET /tasks`.
+    filter: TaskFilter,
+    tasks: Vec<Task>,
+    provider: String,
+    model: String,
+}
+
+/// JSON schema of a `TaskFilter`; every field is required but nullable, as strict
+/// structured-output modes require.
+fn task_filter_schema() -> serde_json::Value {
+    let text = serde_json::json!({ "type": ["string", "null"] });
+    let flag = serde_json::json!({ "type": ["boolean", "null"] });
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "project": text,
+            "assignee": text,
+            "status": text,
+            "tag": text,
+            "in_sprint": flag,
+            "archived": flag,
+            "search": text,
+            "deadline_before": text,
+            "deadline_after": text
+        },
+        "required": ["project", "assignee", "status", "tag", "in_sprint", "archived", "search", "deadline_before", "deadline_after"],
+        "additionalProperties": false
+    })
+}
+
+/// Parses the model's filter, dropping empty values and rejecting unknown statuses and
+/// malformed dates.
+fn parse_task_filter(content: &str) -> std::result::Result<TaskFilter, String> {
+    let json_str = extract_json_from_response(content).unwrap_or(content.trim());
+    let mut filter: TaskFilter = serde_json::from_str(json_str).map_err(|e| e.to_string())?;
+    for value in [
+        &mut filter.project,
+        &mut filter.assignee,
+        &mut filter.status,
+        &mut filter.tag,
+        &mut filter.search,
+        &mut filter.deadline_before,
+        &mut filter.deadline_after,
+    ] {
+        if value.as_deref().is_some_and(|v| v.trim().is_empty()) {
+            *value = None;
+        }
+    }
+    if let Some(status) = filter_values(&filter.status).into_iter().find(|s| !VALID_STATUSES.contains(s)) {
+        return Err(format!("unknown status \"{}\"", status));
+    }
+    for date in [&filter.deadline_before, &filter.deadline_after].into_iter().flatten() {
+        if !is_iso_date(date) {
+            return Err(format!("\"{}\" is not a YYYY-MM-DD date", date));
+        }
+    }
+    Ok(filter)
+}
+
+/// Replaces each comma-separated name in `value` with the existing name it refers to.
+/// Names that match nothing are kept, so the filter shows why nothing was found.
+fn resolve_filter_names(known: &[KnownName], value: &mut Option<String>) {
+    let Some(names) = value.as_deref() else { return };
+    let resolved: Vec<String> = names
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(|n| match resolve_name(known, n) {
+            ResolvedName::Matched(name, _) => name,
+            _ => n.to_string(),
+        })
+        .collect();
+    *value = Some(resolved.join(","));
+}
+
+/// Translates a question such as "what is Alice blocked on in Backend?" into a `TaskFilter`
+/// with the AI provider and returns the matching tasks together with the filter. The
+/// model only ever produces a filter; the query is built from it like for `GET /tasks`.
+async fn ask_tasks(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<AskRequest>,
+) -> Result<HttpResponse> {
+    let question = body.into_inner().question.trim().to_string();
+    if question.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "question is required" })));
+    }
+    if let Some(resp) = ai_unavailable(&data) {
+        return Ok(resp);
+    }
+    if let Some(resp) = ai_over_budget(&data).await? {
+        return Ok(resp);
+    }
+
+    let template = latest_prompt(&data.pool, QUERY_PROMPT)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("task query prompt is missing"))?;
+    let (projects, assignees) = load_known_names(&data.pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let system = render_prompt(
+        &template.template,
+        &[
+            ("today", data.deadlines.today().format("%Y-%m-%d").to_string()),
+            ("projects", describe_names(&projects)),
+            ("assignees", describe_names(&assignees)),
+            ("conventions", template.conventions),
+        ],
+    );
+    let ai = MeteredProvider {
+        inner: data.ai.clone(),
+        data: data.clone(),
+        operation: "task_query",
+        user: request_user(&req),
+        job_id: None,
+    };
+    let messages = vec![ChatMessage::system(system), ChatMessage::user(question.clone())];
+    let (mut filter, completion) =
+        match complete_parsed(&ai, messages, "task_filter", &task_filter_schema(), "a task filter", parse_task_filter).await {
+            Ok(parsed) => parsed,
+            Err(e) => return Ok(e.into_response()),
+        };
+    resolve_filter_names(&projects, &mut filter.project);
+    resolve_filter_names(&assignees, &mut filter.assignee);
+    filter.archived.get_or_insert(false);
+
+    let mut conn = data.pool.acquire().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let tasks = query_tasks(&mut conn, &filter).await?;
+    Ok(HttpResponse::Ok().json(AskResponse {
+        question,
+        filter,
+        tasks,
+        provider: completion.provider,
+        model: completion.model,
+    }))
+}
+
+/// A subtask proposed by `POST /tasks/{id}/breakdown`, and the shape `POST /tasks/{id}/subtasks`
+/// accepts, so a preview can be edited and sent back.
+#[derive(Serialize, Deserialize, Clone)]
+struct SubtaskDraft {
+    title: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    estimate_hours: Option<f64>,
+    /// Defaults to the parent's assignee.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    assignee: Option<String>,
+    /// Defaults to the parent's deadline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deadline: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TaskBreakdown {
+    task_id: i64,
+    subtasks: Vec<SubtaskDraft>,
+    total_estimate_hours: f64,
+    provider: String,
+    model: String,
+}
+
+fn task_breakdown_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "subtasks": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "title": { "type": "string" },
+                        "description": { "type": "string" },
+                        "tags": { "type": "array", "items": { "type": "string" } },
+                        "estimate_hours": { "type": "number" }
+                    },
+                    "required": ["title", "description", "tags", "estimate_hours"],
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": ["subtasks"],
+        "additionalProperties": false
+    })
+}
+
+/// Returns the proposed subtasks, accepting `{"subtasks": [...]}` as well as a bare array.
+/// Items without a title are dropped and unusable estimates cleared.
+fn parse_breakdown(content: &str) -> std::result::Result<Vec<SubtaskDraft>, String> {
+    let json_str = extract_json_from_response(content).unwrap_or(content.trim());
+    let value: serde_json::Value = serde_json::from_str(json_str).map_err(|e| e.to_string())?;
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Object(mut obj) => match obj.remove("subtasks") {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => return Err("expected an object with a \"subtasks\" array".to_string()),
+        },
+        _ => return Err("expected an object with a \"subtasks\" array".to_string()),
+    };
+    let subtasks: Vec<SubtaskDraft> = items
+        .into_iter()
+        .filter_map(|item| serde_json::from_value::<SubtaskDraft>(item).ok())
+        .filter(|s| !s.title.trim().is_empty())
+        .map(|s| SubtaskDraft {
+            title: s.title.trim().to_string(),
+            description: s.description.trim().to_string(),
+            tags: s.tags.iter().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect(),
+            estimate_hours: s.estimate_hours.filter(|h| h.is_finite() && *h > 0.0 && *h <= 10000.0),
+            assignee: None,
+            deadline: None,
+        })
+        .take(20)
+        .collect();
+    if subtasks.is_empty() {
+        return Err("no subtasks with a title".to_string());
+    }
+    Ok(subtasks)
+}
+
+/// Asks the provider to break a task down into subtasks with estimates. Nothing is created:
+/// the preview can be edited and sent to `POST /tasks/{id}/subtasks`.
+async fn breakdown_task(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse> {
+    let mut conn = data.pool.acquire().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(task) = fetch_task(&mut conn, path.into_inner()).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    };
+    let existing = query_tasks(
+        &mut conn,
+        &TaskFilter {
+            parent_id: Some(task.id),
+            ..Default::default()
+        },
+    )
+    .await?;
+    drop(conn);
+    if let Some(resp) = ai_unavailable(&data) {
+        return Ok(resp);
+    }
+    if let Some(resp) = ai_over_budget(&data).await? {
+        return Ok(resp);
+    }
+
+    let template = latest_prompt(&data.pool, BREAKDOWN_PROMPT)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("task breakdown prompt is missing"))?;
+    let system = render_prompt(
+        &template.template,
+        &[
+            ("today", data.deadlines.today().format("%Y-%m-%d").to_string()),
+            ("conventions", template.conventions),
+        ],
+    );
+    let mut facts = format!(
+        "Break down this task.\n\nTitle: {}\nProject: {}\nTags: {}\nDeadline: {}\nDescription: {}\n",
+        task.title,
+        task.project,
+        task.tags.join(", "),
+        task.deadline.as_deref().unwrap_or("none"),
+        clip(&task.description, 2000)
+    );
+    if let Some(notes) = &task.notes {
+        facts.push_str(&format!("Notes: {}\n", clip(notes, 1000)));
+    }
+    if !existing.is_empty() {
+        facts.push_str("\nExisting subtasks:\n");
+        for subtask in &existing {
+            facts.push_str(&format!("- {} ({})\n", subtask.title, subtask.status));
+        }
+    }
+    let ai = MeteredProvider {
+        inner: data.ai.clone(),
+        data: data.clone(),
+        operation: "task_breakdown",
+        user: request_user(&req),
+        job_id: None,
+    };
+    let messages = vec![ChatMessage::system(system), ChatMessage::user(facts)];
+    let (subtasks, completion) =
+        match complete_parsed(&ai, messages, "subtasks", &task_breakdown_schema(), "subtasks", parse_breakdown).await {
+            Ok(parsed) => parsed,
+            Err(e) => return Ok(e.into_response()),
         };
-        let status = item
-            .get("status")
-            .and_then(|v| v.as_str())
-            .filter(|s| VALID_STATUSES.contains(s))
-            .unwrap_or("todo")
-            .to_string();
+    Ok(HttpResponse::Ok().json(TaskBreakdown {
+        task_id: task.id,
+        total_estimate_hours: subtasks.iter().filter_map(|s| s.estimate_hours).sum(),
+        subtasks,
+        provider: completion.provider,
+        model: completion.model,
+    }))
+}
+
+#[derive(Deserialize)]
+struct CreateSubtasksRequest {
+    subtasks: Vec<SubtaskDraft>,
+}
+
+/// Creates subtasks of a task in its project. They start as `todo` and take the parent's
+/// assignee and deadline unless given. Either all are created or none.
+async fn create_subtasks(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: web::Json<CreateSubtasksRequest>,
+) -> Result<HttpResponse> {
+    let mut conn = data.pool.acquire().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(parent) = fetch_task(&mut conn, path.into_inner()).await? else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "task not found" })));
+    };
+    drop(conn);
+    let drafts = body.into_inner().subtasks;
+    if drafts.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "subtasks must not be empty" })));
+    }
+    if drafts.len() > 50 {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "at most 50 subtasks can be created at once" })));
+    }
 
-        let task = Task {
+    let mut tasks = Vec::with_capacity(drafts.len());
+    for (index, draft) in drafts.into_iter().enumerate() {
+        let mut task = Task {
             id: 0,
-            title: title.clone(),
-            description: description.clone(),
-            tags: tags.clone(),
-            deadline: deadline.clone(),
-            project: project.clone(),
-            assignee: assignee.clone(),
-            status: status.clone(),
+            title: draft.title.trim().to_string(),
+            description: draft.description,
+            tags: draft.tags,
+            deadline: draft.deadline.or_else(|| parent.deadline.clone()),
+            project: parent.project.clone(),
+            assignee: draft.assignee.unwrap_or_else(|| parent.assignee.clone()),
+            status: "todo".to_string(),
             in_sprint: false,
             notes: None,
             archived: false,
-        };
-
-        if validate_task(&task).is_none() {
-            let tags_json = serde_json::to_string(&tags)
-                .map_err(actix_web::error::ErrorInternalServerError)?;
-            let id = sqlx::query_scalar::<_, i64>(
-                r#"
-                INSERT INTO tasks (title, description, tags, deadline, project, assignee, status, in_sprint, notes)
-                VALUES (?, ?, ?, ?, ?, ?, ?, 0, ?)
-                RETURNING id
-                "#,
-            )
-            .bind(&title)
-            .bind(&description)
-            .bind(&tags_json)
-            .bind(&deadline)
-            .bind(&project)
-            .bind(&assignee)
-            .bind(&status)
-            .bind("")
-            .fetch_one(&data.pool)
+            rank: String::new(),
+            key: None,
+            meeting_id: None,
+            prompt_version: None,
+            parent_id: Some(parent.id),
+            estimate_hours: draft.estimate_hours,
+        };
+        if let Some(msg) = prepare_new_task(&data, &mut task)
             .await
-            .map_err(actix_web::error::ErrorInternalServerError)?;
-
-            created.push(Task {
-                id,
-                title,
-                description,
-                tags,
-                deadline,
-                project,
-                assignee,
-                status,
-                in_sprint: false,
-                notes: None,
-                archived: false,
-            });
+            .map_err(actix_web::error::ErrorInternalServerError)?
+        {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg, "index": index })));
         }
+        tasks.push(task);
     }
 
+    let mut tx = data.pool.begin().await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let mut created = Vec::with_capacity(tasks.len());
+    for (index, task) in tasks.into_iter().enumerate() {
+        match insert_within_wip(&mut tx, task).await? {
+            Ok((task, warnings)) => created.push(TaskResponse { task, warnings }),
+            Err(reject) => {
+                return Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({
+                    "error": reject.error,
+                    "limit": reject.limit,
+                    "current": reject.current,
+                    "index": index,
+                })))
+            }
+        }
+    }
+    tx.commit().await.map_err(actix_web::error::ErrorInternalServerError)?;
     Ok(HttpResponse::Created().json(created))
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     let pool = match std::env::var("DATABASE_URL") {
         Ok(url) => SqlitePoolOptions::new()
             .max_connections(5)
@@ -922,7 +7941,31 @@ async fn main() -> std::io::Result<()> {
 
     init_db(&pool).await.expect("Failed to initialize database");
 
-    let app_state = web::Data::new(AppState { pool });
+    let http = AiHttp::from_env();
+    let app_state = web::Data::new(AppState {
+        pool,
+        ai: Arc::new(resolve_ai_provider(&http)),
+        http,
+        extraction: ExtractionConfig::from_env(),
+        deadlines: DeadlineConfig::from_env(),
+        budget: AiBudget::from_env(),
+        job_notify: tokio::sync::Notify::new(),
+        job_max_attempts: std::env::var("JOB_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(3),
+    });
+
+    resume_jobs(&app_state.pool).await.expect("Failed to resume jobs");
+    let workers = std::env::var("JOB_WORKERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &usize| *v > 0)
+        .unwrap_or(2);
+    for _ in 0..workers {
+        actix_web::rt::spawn(job_worker(app_state.clone()));
+    }
 
     HttpServer::new(move || {
         let cors = Cors::default()
@@ -937,21 +7980,55 @@ async fn main() -> std::io::Result<()> {
                     || origin.as_bytes().starts_with(b"https://192.168.")
             })
             .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
-            .allowed_headers(vec![actix_web::http::header::CONTENT_TYPE]);
+            .allowed_headers(vec![
+                actix_web::http::header::CONTENT_TYPE,
+                actix_web::http::header::AUTHORIZATION,
+                actix_web::http::header::HeaderName::from_static("x-user"),
+            ]);
         App::new()
             .wrap(cors)
             .app_data(app_state.clone())
+            // Optional JSON bodies are read as bytes; allow them as much as `web::Json` allows.
+            .app_data(web::PayloadConfig::new(2 * 1024 * 1024))
             .route("/projects", web::get().to(get_projects))
             .route("/projects", web::post().to(create_project))
             .route("/projects/{id}", web::delete().to(delete_project))
+            .route("/projects/{id}/transition-rules", web::get().to(get_transition_rules))
+            .route("/projects/{id}/transition-rules", web::put().to(set_transition_rules))
+            .route("/projects/{id}/wip-limits", web::get().to(get_wip_limits))
+            .route("/projects/{id}/wip-limits", web::put().to(set_wip_limits))
+            .route("/wip-limits", web::get().to(get_wip_usage))
+            .route("/jobs/{id}", web::get().to(get_job))
+            .route("/jobs/{id}/cancel", web::post().to(cancel_job))
+            .route("/meetings", web::get().to(get_meetings))
+            .route("/meetings/{id}", web::get().to(get_meeting))
+            .route("/meetings/{id}/extract", web::post().to(extract_meeting_tasks))
+            .route("/ai/usage", web::get().to(get_ai_usage))
+            .route("/sprints/current/summary", web::post().to(summarize_current_sprint))
+            .route("/admin/prompts", web::get().to(list_prompts))
+            .route("/admin/prompts/{name}", web::put().to(update_prompt))
+            .route("/admin/prompts/{name}/versions", web::get().to(list_prompt_versions))
             .route("/assignees", web::get().to(get_assignees))
             .route("/assignees", web::post().to(create_assignee))
             .route("/assignees/{id}", web::delete().to(delete_assignee))
+            .route("/assignees/{id}/aliases", web::put().to(update_assignee_aliases))
             .route("/tasks", web::get().to(get_tasks))
             .route("/tasks", web::post().to(create_task))
+            .route("/tasks/generate", web::post().guard(guard::fn_guard(is_multipart)).to(generate_tasks_from_upload))
             .route("/tasks/generate", web::post().to(generate_tasks_from_ai))
+            .route("/tasks/generate/{draft_id}", web::get().to(get_task_draft))
+            .route("/tasks/generate/{draft_id}", web::delete().to(delete_task_draft))
+            .route("/tasks/generate/{draft_id}/commit", web::post().to(commit_task_draft))
+            .route("/tasks/bulk", web::post().to(bulk_tasks))
+            .route("/tasks/ask", web::post().to(ask_tasks))
+            .route("/tasks/{id}", web::get().to(get_task))
             .route("/tasks/{id}", web::put().to(update_task))
             .route("/tasks/{id}/status", web::put().to(update_task_status))
+            .route("/tasks/{id}/move", web::put().to(move_task))
+            .route("/tasks/{id}/similar", web::get().to(get_similar_tasks))
+            .route("/tasks/{id}/suggestions", web::get().to(get_task_suggestions))
+            .route("/tasks/{id}/breakdown", web::post().to(breakdown_task))
+            .route("/tasks/{id}/subtasks", web::post().to(create_subtasks))
             .route("/tasks/{id}/sprint", web::put().to(update_task_sprint))
             .route("/tasks/archive-done-in-sprint", web::post().to(archive_done_in_sprint))
             .route("/tasks/{id}/unarchive", web::put().to(unarchive_task))
@@ -961,3 +8038,1177 @@ async fn main() -> std::io::Result<()> {
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known(names: &[&str]) -> Vec<KnownName> {
+        names
+            .iter()
+            .map(|name| KnownName {
+                name: name.to_string(),
+                aliases: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn matched(resolved: ResolvedName) -> Option<(String, NameMatch)> {
+        match resolved {
+            ResolvedName::Matched(name, how) => Some((name, how)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn resolve_name_matches_nicknames() {
+        let names = known(&["Robert Smith", "Alice Jones"]);
+        assert_eq!(
+            matched(resolve_name(&names, "Bob")),
+            Some(("Robert Smith".to_string(), NameMatch::Nickname))
+        );
+        assert_eq!(
+            matched(resolve_name(&names, "Bob Smith")),
+            Some(("Robert Smith".to_string(), NameMatch::Nickname))
+        );
+    }
+
+    #[test]
+    fn resolve_name_matches_initials_and_parts() {
+        let names = known(&["Robert Smith", "Alice Jones"]);
+        assert_eq!(
+            matched(resolve_name(&names, "R. Smith")),
+            Some(("Robert Smith".to_string(), NameMatch::Partial))
+        );
+        assert_eq!(
+            matched(resolve_name(&names, "Jones")),
+            Some(("Alice Jones".to_string(), NameMatch::Partial))
+        );
+    }
+
+    #[test]
+    fn resolve_name_reports_the_method_of_the_surviving_candidate() {
+        // "Bob" is a nickname for Robert Jones, but only Bob Smith matches every part.
+        let names = known(&["Robert Jones", "Bob Smith"]);
+        assert_eq!(
+            matched(resolve_name(&names, "Bob S")),
+            Some(("Bob Smith".to_string(), NameMatch::Partial))
+        );
+    }
+
+    #[test]
+    fn resolve_name_matches_typos() {
+        let names = known(&["Robert Smith", "Alice Jones"]);
+        assert_eq!(
+            matched(resolve_name(&names, "Robret Smith")),
+            Some(("Robert Smith".to_string(), NameMatch::Typo))
+        );
+        assert!(matches!(resolve_name(&names, "Xavier"), ResolvedName::Unresolved));
+    }
+
+    #[test]
+    fn resolve_name_reports_ambiguous_matches() {
+        let names = known(&["Robert Smith", "Anna Smith", "Alice Jones"]);
+        match resolve_name(&names, "Smith") {
+            ResolvedName::Ambiguous(candidates) => assert_eq!(candidates, vec!["Robert Smith", "Anna Smith"]),
+            _ => panic!("expected an ambiguous match"),
+        }
+        assert!(matches!(resolve_name(&names, "Robert Smith"), ResolvedName::Exact));
+    }
+
+    #[tokio::test]
+    async fn update_keeps_the_estimate_unless_sent() {
+        let data = test_state().await;
+        let task = add_task(&data, "estimated", "todo").await;
+        sqlx::query("UPDATE tasks SET estimate_hours=3 WHERE id=?")
+            .bind(task.id)
+            .execute(&data.pool)
+            .await
+            .unwrap();
+        let mut body = serde_json::to_value(&task).unwrap();
+        body.as_object_mut().unwrap().remove("estimate_hours");
+        let changes = [
+            (None, Some(3.0)),
+            (Some(serde_json::json!(5.5)), Some(5.5)),
+            (Some(serde_json::Value::Null), None),
+        ];
+        for (change, expected) in changes {
+            if let Some(change) = change {
+                body["estimate_hours"] = change;
+            }
+            let request = serde_json::from_value(body.clone()).unwrap();
+            let (status, updated) =
+                body_json(update_task(data.clone(), web::Path::from(task.id), web::Json(request)).await.unwrap()).await;
+            assert_eq!(status, 200, "{}", updated);
+            assert_eq!(updated["estimate_hours"].as_f64(), expected);
+            let mut conn = data.pool.acquire().await.unwrap();
+            assert_eq!(fetch_task(&mut conn, task.id).await.unwrap().unwrap().estimate_hours, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_embeddings_reuse_current_rows_and_embed_the_rest() {
+        let data = test_state().await;
+        let cached = add_task(&data, "Rotate keys", "todo").await;
+        let stale = add_task(&data, "Renew certificates", "todo").await;
+        let fresh = add_task(&data, "Ship v2", "todo").await;
+        for (task, content) in [(&cached, similarity_text(&cached)), (&stale, "old title".to_string())] {
+            sqlx::query("INSERT INTO task_embeddings (task_id, model, content, embedding) VALUES (?, 'fake', ?, '[1.0]')")
+                .bind(task.id)
+                .bind(content)
+                .execute(&data.pool)
+                .await
+                .unwrap();
+        }
+        let ai = FakeProvider {
+            model: "fake".to_string(),
+            response: None,
+            embeddings: true,
+        };
+
+        let mut conn = data.pool.acquire().await.unwrap();
+        let tasks = [cached.clone(), stale.clone(), fresh.clone()];
+        let vectors = cached_embeddings(&mut conn, &ai, "fake", &tasks).await.unwrap().unwrap();
+        assert_eq!(vectors[0], vec![1.0]);
+        assert_eq!(vectors[1].len(), 64);
+        assert_eq!(vectors[2].len(), 64);
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM task_embeddings WHERE content != 'old title'")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(stored, 3);
+        assert_eq!(cached_embeddings(&mut conn, &ai, "fake", &[]).await.unwrap(), Some(Vec::new()));
+    }
+
+    #[tokio::test]
+    async fn suggestions_come_from_related_history() {
+        let data = test_state().await;
+        sqlx::query("INSERT INTO assignees (name) VALUES ('Alice'), ('Bob')")
+            .execute(&data.pool)
+            .await
+            .unwrap();
+        let mut conn = data.pool.acquire().await.unwrap();
+        for (title, assignee, tags) in [
+            ("Fix login redirect", "Alice", vec!["auth"]),
+            ("Fix login timeout", "Alice", vec!["auth"]),
+            ("Login page copy", "Bob", vec![]),
+            ("Rotate signing keys", "Bob", vec!["security"]),
+            ("Quarterly planning", "Bob", vec![]),
+        ] {
+            let mut task = new_task(title, "done");
+            task.assignee = assignee.to_string();
+            task.tags = tags.into_iter().map(String::from).collect();
+            insert_task(&mut conn, task).await.unwrap();
+        }
+        drop(conn);
+
+        let mut probe = new_task("Fix login on mobile", "todo");
+        probe.tags = vec!["security".to_string()];
+        let history = TaskHistory::load(&data.pool, std::slice::from_ref(&probe)).await.unwrap();
+        let mut titles: Vec<&str> = history.tasks.iter().map(|t| t.title.as_str()).collect();
+        titles.sort_unstable();
+        assert_eq!(titles, vec!["Fix login redirect", "Fix login timeout", "Login page copy", "Rotate signing keys"]);
+        let suggestions = history.suggest(&probe);
+        assert_eq!(suggestions.assignee.map(|s| s.value), Some("Alice".to_string()));
+        assert_eq!(suggestions.tags.first().map(|s| s.value.as_str()), Some("auth"));
+
+        let history = TaskHistory::load(&data.pool, &[new_task("", "todo")]).await.unwrap();
+        assert!(history.tasks.is_empty());
+    }
+
+    #[test]
+    fn transition_rules_name_the_move_they_refuse() {
+        let rule = |kind: &str, from: Option<&str>, to: Option<&str>| TransitionRule {
+            kind: kind.to_string(),
+            from_status: from.map(String::from),
+            to_status: to.map(String::from),
+        };
+        let task = new_task("t", "done");
+        let any_move = rule("require_comment", None, None);
+        assert!(any_move.matches("todo", "done") && any_move.matches("blocked", "in_progress"));
+        assert_eq!(
+            any_move.violation("todo", &task, Some("  ")).as_deref(),
+            Some("moving from 'todo' to 'done' requires a comment")
+        );
+        assert_eq!(any_move.violation("todo", &task, Some("shipped")), None);
+
+        let to_done = rule("require_assignee", None, Some("done"));
+        assert!(to_done.matches("todo", "done") && !to_done.matches("done", "todo"));
+        assert_eq!(
+            to_done.violation("todo", &task, None).as_deref(),
+            Some("moving to 'done' requires an assignee")
+        );
+        let from_blocked = rule("require_notes", Some("blocked"), None);
+        assert!(from_blocked.matches("blocked", "todo") && !from_blocked.matches("todo", "blocked"));
+        assert!(from_blocked.violation("blocked", &task, None).is_some());
+    }
+
+    async fn set_wip_limit(data: &AppState, status: &str, max_tasks: i64, mode: &str) {
+        sqlx::query("DELETE FROM wip_limits").execute(&data.pool).await.unwrap();
+        sqlx::query("INSERT INTO wip_limits (project_id, status, scope, max_tasks, mode) VALUES (1, ?, 'project', ?, ?)")
+            .bind(status)
+            .bind(max_tasks)
+            .bind(mode)
+            .execute(&data.pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn wip_limits_apply_to_moves_and_new_tasks() {
+        let data = test_state().await;
+        set_wip_limit(&data, "in_progress", 1, "reject").await;
+        add_task(&data, "busy", "in_progress").await;
+        let waiting = add_task(&data, "waiting", "todo").await;
+
+        let request = UpdateStatusRequest {
+            status: "in_progress".to_string(),
+            comment: None,
+        };
+        let (status, body) =
+            body_json(update_task_status(data.clone(), web::Path::from(waiting.id), web::Json(request)).await.unwrap())
+                .await;
+        assert_eq!(status, 422);
+        assert_eq!(body["current"], 1);
+
+        let mut conn = data.pool.acquire().await.unwrap();
+        let refused = insert_within_wip(&mut conn, new_task("another", "in_progress")).await.unwrap();
+        assert_eq!(refused.err().map(|e| e.limit.max_tasks), Some(1));
+        sqlx::query("UPDATE tasks SET archived=1 WHERE id=?").bind(waiting.id).execute(&mut *conn).await.unwrap();
+        sqlx::query("UPDATE tasks SET status='in_progress' WHERE id=?").bind(waiting.id).execute(&mut *conn).await.unwrap();
+        drop(conn);
+        let (status, _) = body_json(unarchive_task(data.clone(), web::Path::from(waiting.id)).await.unwrap()).await;
+        assert_eq!(status, 422);
+
+        set_wip_limit(&data, "in_progress", 1, "warn").await;
+        let (status, body) = body_json(unarchive_task(data.clone(), web::Path::from(waiting.id)).await.unwrap()).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["warnings"][0], "General already has 1 'in_progress' tasks (limit 1)");
+        let mut conn = data.pool.acquire().await.unwrap();
+        let Ok((_, warnings)) = insert_within_wip(&mut conn, new_task("third", "in_progress")).await.unwrap() else {
+            panic!("a warn limit refused the task");
+        };
+        assert_eq!(warnings, vec!["General already has 2 'in_progress' tasks (limit 1)"]);
+        drop(conn);
+
+        let filter = web::Query(TaskFilter::default());
+        let list = web::Query(TaskListQuery {
+            expand: Some("wip".to_string()),
+        });
+        let (status, body) = body_json(get_tasks(data.clone(), filter, list).await.unwrap()).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["tasks"].as_array().unwrap().len(), 3);
+        assert_eq!((body["wip"][0]["current"].as_i64(), body["wip"][0]["over_limit"].as_bool()), (Some(3), Some(true)));
+    }
+
+    #[test]
+    fn chunk_tasks_merge_only_across_chunks() {
+        let mut extracted = Vec::new();
+        merge_chunk_tasks(
+            &mut extracted,
+            0,
+            vec![new_task("Update docs for v1", "todo"), new_task("Update docs for v2", "todo")],
+        );
+        assert_eq!(extracted.len(), 2);
+        let mut repeated = new_task("Update docs for v2", "todo");
+        repeated.tags = vec!["docs".to_string()];
+        merge_chunk_tasks(&mut extracted, 1, vec![repeated, new_task("Update docs for v2.1", "todo")]);
+        let titles: Vec<(usize, &str)> = extracted.iter().map(|(c, t)| (*c, t.title.as_str())).collect();
+        assert_eq!(titles, vec![(0, "Update docs for v1"), (0, "Update docs for v2"), (1, "Update docs for v2.1")]);
+        assert_eq!(extracted[1].1.tags, vec!["docs"]);
+    }
+
+    #[test]
+    fn rank_after_carries_instead_of_growing() {
+        assert_eq!(rank_after(""), "i");
+        assert_eq!(rank_after("i"), "j");
+        assert_eq!(rank_after("az"), "b1");
+        assert_eq!(rank_after("z"), "z1");
+        assert_eq!(rank_after("zz"), "zz01");
+        let mut rank = "i".to_string();
+        for _ in 0..1000 {
+            let next = rank_after(&rank);
+            assert!(next > rank && !next.ends_with('0'), "{} after {}", next, rank);
+            rank = next;
+        }
+        assert!(rank.len() <= 4, "{}", rank);
+    }
+
+    #[test]
+    fn rank_between_stays_between() {
+        for (lo, hi) in [("", "i"), ("a", "b"), ("a", "a1"), ("az", "b"), ("i", "i01"), ("", "01")] {
+            let mid = rank_between(lo, Some(hi));
+            assert!(lo < mid.as_str() && mid.as_str() < hi, "{} not between {} and {}", mid, lo, hi);
+            assert!(!mid.ends_with('0'));
+        }
+        assert_eq!(rank_between("i", None), "j");
+    }
+
+    #[tokio::test]
+    async fn status_changes_move_tasks_to_the_bottom_of_the_column() {
+        let data = test_state().await;
+        let first = add_task(&data, "first", "todo").await;
+        let second = add_task(&data, "second", "todo").await;
+        let third = add_task(&data, "third", "todo").await;
+        let mut ranks = Vec::new();
+        for id in [first.id, second.id] {
+            let request = UpdateStatusRequest {
+                status: "in_progress".to_string(),
+                comment: None,
+            };
+            let (status, body) = body_json(
+                update_task_status(data.clone(), web::Path::from(id), web::Json(request)).await.unwrap(),
+            )
+            .await;
+            assert_eq!(status, 200);
+            ranks.push(body["rank"].as_str().unwrap().to_string());
+        }
+        assert!(ranks[0] < ranks[1], "{:?}", ranks);
+
+        let request = MoveTaskRequest {
+            status: "in_progress".to_string(),
+            after_id: Some(first.id),
+            before_id: Some(second.id),
+            comment: None,
+        };
+        let (status, body) =
+            body_json(move_task(data.clone(), web::Path::from(third.id), web::Json(request)).await.unwrap()).await;
+        assert_eq!(status, 200, "{}", body);
+        let rank = body["rank"].as_str().unwrap();
+        assert!(ranks[0].as_str() < rank && rank < ranks[1].as_str());
+    }
+
+    fn speaker_labels(notes: &str, projects: &[KnownName]) -> Vec<String> {
+        transcript_speakers(notes, projects, &known(&["Alice Jones", "Bob Lee"]))
+            .into_iter()
+            .map(|s| s.label)
+            .collect()
+    }
+
+    #[test]
+    fn transcript_speakers_are_found_in_turns() {
+        let notes = "Alice: I'll handle the migration.\n- Bob: I can review it.\n[Alice] Thanks.\nSomething unrelated.";
+        assert_eq!(speaker_labels(notes, &[]), vec!["Alice", "Bob"]);
+    }
+
+    #[test]
+    fn transcript_speakers_skip_projects_and_labels() {
+        let projects = vec![KnownName {
+            name: "Backend".to_string(),
+            aliases: vec!["BE".to_string()],
+        }];
+        let notes = "Backend: deploy tomorrow\nAlice: agreed\nBE: rotate keys\nBob: fine\nDecision: ship it\nAlice: done";
+        assert_eq!(speaker_labels(notes, &projects), vec!["Alice", "Bob"]);
+        assert!(speaker_labels("Backend: deploy tomorrow\nBE: rotate keys", &projects).is_empty());
+    }
+
+    #[test]
+    fn transcript_speakers_need_turns_to_dominate() {
+        let notes = "Weekly sync\n\nWe went over the release plan.\nThe API is nearly done.\n\
+                     Mobile is blocked on design.\nAlice: owns the rollout\nBob: owns QA";
+        assert!(speaker_labels(notes, &[]).is_empty());
+        assert!(speaker_labels("Alice: one turn\nNot a turn", &[]).is_empty());
+    }
+
+    #[test]
+    fn caption_speaker_reads_voice_tags_brackets_and_prefixes() {
+        let speaker = |text: &str| caption_speaker(text).0;
+        assert_eq!(speaker("<v Alice Smith>Hello"), Some("Alice Smith".to_string()));
+        assert_eq!(speaker("<v.loud.yell Bob>Stop"), Some("Bob".to_string()));
+        assert_eq!(speaker("[Alice] Hello"), Some("Alice".to_string()));
+        assert_eq!(speaker(">> Bob Lee: Hello"), Some("Bob Lee".to_string()));
+        assert_eq!(speaker("Note: the recording started late"), None);
+        assert_eq!(speaker("See https://example.com"), None);
+        assert_eq!(speaker("lowercase: not a name"), None);
+    }
+
+    #[test]
+    fn caption_text_reads_vtt() {
+        let vtt = "WEBVTT\n\nNOTE written by hand\n\n\
+                   1\n00:00:01.000 --> 00:00:03.000\n<v.loud Alice>I'll handle the <b>migration</b>\n\n\
+                   00:00:03.000 --> 00:00:05.000 align:start\n<v Alice>by Friday &amp; test it\n\n\
+                   00:00:05.000 --> 00:00:06.000\n<v Bob>Thanks</v>\n\n\
+                   00:00:06.000 --> 00:00:08.000\nNote: the recording started late\n";
+        assert_eq!(
+            caption_text(vtt),
+            "Alice: I'll handle the migration by Friday & test it\n\
+             Bob: Thanks Note: the recording started late"
+        );
+    }
+
+    #[test]
+    fn caption_text_reads_srt_with_crlf() {
+        let srt = "1\r\n00:00:01,000 --> 00:00:02,000\r\nAlice: Ship it\r\n\r\n\
+                   2\r\n00:00:02,000 --> 00:00:04,000\r\n[Bob] On it\r\ntoday\r\n\r\n";
+        assert_eq!(caption_text(srt), "Alice: Ship it\nBob: On it today");
+    }
+
+    #[test]
+    fn caption_text_drops_repeated_rolling_captions() {
+        let vtt = "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nAlice: deploy tonight\n\n\
+                   00:00:02.000 --> 00:00:03.000\nAlice: deploy tonight\n\n\
+                   00:00:03.000 --> 00:00:04.000\ndeploy tonight\n\n\
+                   00:00:04.000 --> 00:00:05.000\nAlice: then roll back\n";
+        assert_eq!(caption_text(vtt), "Alice: deploy tonight then roll back");
+    }
+
+    fn docx(document: &str) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("word/document.xml", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(document.as_bytes()).unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn docx_text_reads_paragraphs_tabs_and_breaks() {
+        let bytes = docx(
+            r#"<?xml version="1.0"?><w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body><w:p><w:r><w:t>Owner</w:t><w:tab/><w:t>Alice &amp; Bob</w:t></w:r></w:p><w:p><w:r><w:t xml:space="preserve">Ship </w:t><w:br/><w:t>v2</w:t></w:r></w:p></w:body></w:document>"#,
+        );
+        assert_eq!(docx_text(&bytes).unwrap(), "Owner\tAlice & Bob\nShip \nv2\n");
+        assert_eq!(notes_from_file("notes.DOCX", &bytes).unwrap(), "Owner\tAlice & Bob\nShip \nv2");
+    }
+
+    #[test]
+    fn truncated_docx_is_rejected() {
+        let bytes = docx("<w:document><w:body><w:p><w:r><w:t>Ship v2</w:t></w:r></w:p></w:body></w:document>");
+        for len in [0, 10, bytes.len() / 2, bytes.len() - 1] {
+            assert!(notes_from_file("notes.docx", &bytes[..len]).is_err(), "accepted {} bytes", len);
+        }
+        assert!(docx_text(b"not a zip").is_err());
+    }
+
+    async fn memory_pool() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    async fn test_state() -> web::Data<AppState> {
+        let pool = memory_pool().await;
+        init_db(&pool).await.unwrap();
+        web::Data::new(AppState {
+            pool,
+            ai: Arc::new(FakeProvider {
+                model: "fake".to_string(),
+                response: None,
+                embeddings: false,
+            }),
+            http: AiHttp::from_env(),
+            extraction: ExtractionConfig::from_env(),
+            deadlines: DeadlineConfig::from_env(),
+            budget: AiBudget::from_env(),
+            job_notify: tokio::sync::Notify::new(),
+            job_max_attempts: 3,
+        })
+    }
+
+    fn new_task(title: &str, status: &str) -> Task {
+        serde_json::from_value(serde_json::json!({
+            "title": title,
+            "description": "",
+            "tags": [],
+            "deadline": null,
+            "project": "General",
+            "assignee": "Unassigned",
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    async fn add_task(data: &AppState, title: &str, status: &str) -> Task {
+        let mut conn = data.pool.acquire().await.unwrap();
+        insert_task(&mut conn, new_task(title, status)).await.unwrap()
+    }
+
+    async fn body_json(response: HttpResponse) -> (u16, serde_json::Value) {
+        let status = response.status().as_u16();
+        let bytes = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    fn builtin_prompt(name: &str) -> &'static str {
+        PROMPT_TEMPLATES.iter().find(|(n, _)| *n == name).unwrap().1
+    }
+
+    #[tokio::test]
+    async fn upgrade_stores_changed_builtin_prompts_as_new_versions() {
+        let pool = memory_pool().await;
+        // A database from before built-in versions were tracked.
+        sqlx::query(
+            "CREATE TABLE prompt_templates (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                template TEXT NOT NULL,
+                conventions TEXT NOT NULL DEFAULT '',
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (name, version)
+            )",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO prompt_templates (name, version, template, conventions) VALUES \
+             ('task_extraction', 1, 'Old extraction prompt', 'Tag bugs with bug'), \
+             ('sprint_summary', 1, 'Old summary prompt', ''), \
+             ('sprint_summary', 2, 'Summary edited by an admin', '')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        init_db(&pool).await.unwrap();
+        let extraction = latest_prompt(&pool, EXTRACTION_PROMPT).await.unwrap().unwrap();
+        assert_eq!(extraction.version, 2);
+        assert_eq!(extraction.template, builtin_prompt(EXTRACTION_PROMPT));
+        assert_eq!(extraction.conventions, "Tag bugs with bug");
+        assert!(extraction.builtin);
+        let summary = latest_prompt(&pool, SUMMARY_PROMPT).await.unwrap().unwrap();
+        assert_eq!(summary.version, 2);
+        assert_eq!(summary.template, "Summary edited by an admin");
+        assert!(!summary.builtin);
+        let query = latest_prompt(&pool, QUERY_PROMPT).await.unwrap().unwrap();
+        assert_eq!((query.version, query.builtin), (1, true));
+
+        // Starting again with unchanged built-in text adds nothing.
+        init_db(&pool).await.unwrap();
+        assert_eq!(latest_prompt(&pool, EXTRACTION_PROMPT).await.unwrap().unwrap().version, 2);
+    }
+
+    #[tokio::test]
+    async fn legacy_deadlines_that_are_not_dates_move_to_notes() {
+        let pool = memory_pool().await;
+        init_db(&pool).await.unwrap();
+        for (deadline, notes) in [
+            ("ASAP", None),
+            ("Friday", Some("Waiting on design")),
+            (" 2025-03-05 ", None),
+            ("2025-02-30", None),
+            ("2025-03-05", Some("Keep")),
+        ] {
+            sqlx::query(
+                "INSERT INTO tasks (title, description, tags, deadline, project, assignee, status, notes) \
+                 VALUES ('t', '', '[]', ?, 'General', 'Unassigned', 'todo', ?)",
+            )
+            .bind(deadline)
+            .bind(notes)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        migrate_legacy_deadlines(&pool).await.unwrap();
+        let rows: Vec<(Option<String>, Option<String>)> =
+            sqlx::query_as("SELECT deadline, notes FROM tasks ORDER BY id").fetch_all(&pool).await.unwrap();
+        let rows: Vec<(Option<&str>, Option<&str>)> =
+            rows.iter().map(|(d, n)| (d.as_deref(), n.as_deref())).collect();
+        assert_eq!(
+            rows,
+            vec![
+                (None, Some("Deadline: ASAP")),
+                (None, Some("Deadline: Friday\n\nWaiting on design")),
+                (Some("2025-03-05"), None),
+                (None, Some("Deadline: 2025-02-30")),
+                (Some("2025-03-05"), Some("Keep")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn legacy_deadlines_migrate_in_databases_without_notes() {
+        let pool = memory_pool().await;
+        // The tasks table before notes and archiving existed.
+        sqlx::query(
+            "CREATE TABLE tasks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title TEXT NOT NULL,
+                description TEXT NOT NULL,
+                tags TEXT NOT NULL,
+                deadline TEXT,
+                project TEXT NOT NULL,
+                assignee TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'todo',
+                in_sprint INTEGER NOT NULL DEFAULT 0
+            )",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO tasks (title, description, tags, deadline, project, assignee, status) \
+             VALUES ('t', '', '[]', 'next sprint', 'General', 'Unassigned', 'todo')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        init_db(&pool).await.unwrap();
+        let row: (Option<String>, Option<String>, bool) =
+            sqlx::query_as("SELECT deadline, notes, archived FROM tasks").fetch_one(&pool).await.unwrap();
+        assert_eq!(row, (None, Some("Deadline: next sprint".to_string()), false));
+    }
+
+    #[tokio::test]
+    async fn expand_task_includes_the_assignee() {
+        let pool = memory_pool().await;
+        init_db(&pool).await.unwrap();
+        sqlx::query("INSERT INTO assignees (name, aliases) VALUES ('Robert Smith', '[\"Bob\"]')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO tasks (title, description, tags, project, assignee, status) \
+             VALUES ('t', '', '[]', 'General', 'Robert Smith', 'todo') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let mut conn = pool.acquire().await.unwrap();
+        let task = fetch_task(&mut conn, id).await.unwrap().unwrap();
+        let assignee = expand_task(&mut conn, &task, &["assignee"]).await.unwrap().assignee.unwrap();
+        assert_eq!((assignee.name.as_str(), assignee.aliases), ("Robert Smith", vec!["Bob".to_string()]));
+    }
+
+    #[actix_web::test]
+    async fn moved_keys_redirect_with_an_encoded_expand() {
+        let data = test_state().await;
+        let parent = add_task(&data, "parent", "todo").await;
+        let child = add_task(&data, "child", "todo").await;
+        sqlx::query("UPDATE tasks SET key='BE-1' WHERE id=?").bind(parent.id).execute(&data.pool).await.unwrap();
+        sqlx::query("UPDATE tasks SET parent_id=? WHERE id=?")
+            .bind(parent.id)
+            .bind(child.id)
+            .execute(&data.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO task_key_aliases (key, task_id) VALUES ('OLD-7', ?)")
+            .bind(parent.id)
+            .execute(&data.pool)
+            .await
+            .unwrap();
+
+        let get = |reference: &str, expand: &str| {
+            get_task(
+                data.clone(),
+                web::Path::from(reference.to_string()),
+                web::Query(GetTaskQuery { expand: Some(expand.to_string()) }),
+            )
+        };
+        let response = get("OLD-7", " counts , project").await.unwrap();
+        assert_eq!(response.status().as_u16(), 301);
+        assert_eq!(response.headers().get("Location").unwrap(), "/tasks/BE-1?expand=counts%2Cproject");
+
+        let (status, body) = body_json(get("BE-1", "counts").await.unwrap()).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["expanded"]["counts"], serde_json::json!({ "status_changes": 0, "previous_keys": 1, "subtasks": 1 }));
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn deadline_config() -> DeadlineConfig {
+        DeadlineConfig {
+            timezone: chrono_tz::UTC,
+            week_start: Weekday::Mon,
+            week_end: Weekday::Fri,
+            sprint_start: Some(date("2026-01-05")),
+            sprint_length_days: 14,
+        }
+    }
+
+    fn resolve(text: &str, reference: &str) -> Option<NaiveDate> {
+        resolve_deadline(text, date(reference), &deadline_config())
+    }
+
+    #[test]
+    fn resolve_deadline_weekdays() {
+        // 2026-10-16 is a Friday.
+        assert_eq!(resolve("by Friday", "2026-10-16"), Some(date("2026-10-16")));
+        assert_eq!(resolve("Friday", "2026-10-14"), Some(date("2026-10-16")));
+        assert_eq!(resolve("by Monday", "2026-10-16"), Some(date("2026-10-19")));
+        assert_eq!(resolve("next friday", "2026-10-14"), Some(date("2026-10-23")));
+        assert_eq!(resolve("next Friday", "2026-10-16"), Some(date("2026-10-23")));
+    }
+
+    #[test]
+    fn resolve_deadline_end_of_week() {
+        assert_eq!(resolve("end of week", "2026-10-14"), Some(date("2026-10-16")));
+        assert_eq!(resolve("end of week", "2026-10-16"), Some(date("2026-10-16")));
+        // Once Friday has passed, the end of the week is next Friday.
+        assert_eq!(resolve("end of week", "2026-10-17"), Some(date("2026-10-23")));
+        assert_eq!(resolve("end of next week", "2026-10-14"), Some(date("2026-10-23")));
+    }
+
+    #[test]
+    fn resolve_deadline_end_of_week_from_other_week_starts() {
+        for week_start in [Weekday::Sun, Weekday::Sat] {
+            let config = DeadlineConfig {
+                week_start,
+                ..deadline_config()
+            };
+            let resolve = |text, reference| resolve_deadline(text, date(reference), &config);
+            // Wednesday and Friday: this Friday.
+            assert_eq!(resolve("end of week", "2026-10-14"), Some(date("2026-10-16")));
+            assert_eq!(resolve("end of week", "2026-10-16"), Some(date("2026-10-16")));
+            assert_eq!(resolve("end of next week", "2026-10-14"), Some(date("2026-10-23")));
+        }
+        // Saturday already belongs to the next week when weeks start on Saturday.
+        let saturday_weeks = DeadlineConfig {
+            week_start: Weekday::Sat,
+            ..deadline_config()
+        };
+        assert_eq!(resolve_deadline("end of week", date("2026-10-17"), &saturday_weeks), Some(date("2026-10-23")));
+        let thursday_ends = DeadlineConfig {
+            week_end: Weekday::Thu,
+            ..deadline_config()
+        };
+        assert_eq!(resolve_deadline("end of week", date("2026-10-14"), &thursday_ends), Some(date("2026-10-15")));
+    }
+
+    #[test]
+    fn resolve_deadline_month_days() {
+        assert_eq!(resolve("March 5th", "2026-10-16"), Some(date("2027-03-05")));
+        assert_eq!(resolve("March 5th", "2026-03-05"), Some(date("2026-03-05")));
+        assert_eq!(resolve("5 March 2028", "2026-10-16"), Some(date("2028-03-05")));
+        assert_eq!(resolve("February 30", "2026-10-16"), None);
+    }
+
+    #[test]
+    fn resolve_deadline_sprints() {
+        assert_eq!(resolve("end of sprint", "2026-01-05"), Some(date("2026-01-18")));
+        assert_eq!(resolve("end of sprint", "2026-01-18"), Some(date("2026-01-18")));
+        assert_eq!(resolve("end of next sprint", "2026-01-20"), Some(date("2026-02-15")));
+        // Before SPRINT_START_DATE, sprints continue backwards from it.
+        assert_eq!(resolve("end of sprint", "2026-01-01"), Some(date("2026-01-04")));
+        assert_eq!(resolve("end of next sprint", "2026-01-01"), Some(date("2026-01-18")));
+        let no_sprints = DeadlineConfig {
+            sprint_start: None,
+            ..deadline_config()
+        };
+        assert_eq!(resolve_deadline("end of sprint", date("2026-01-05"), &no_sprints), None);
+    }
+
+    #[test]
+    fn resolve_deadline_other_expressions() {
+        assert_eq!(resolve("tomorrow", "2026-12-31"), Some(date("2027-01-01")));
+        assert_eq!(resolve("in two weeks", "2026-10-16"), Some(date("2026-10-30")));
+        assert_eq!(resolve("end of month", "2026-02-10"), Some(date("2026-02-28")));
+        assert_eq!(resolve("2026-11-01", "2026-10-16"), Some(date("2026-11-01")));
+        assert_eq!(resolve("ASAP", "2026-10-16"), None);
+    }
+
+    #[actix_web::test]
+    async fn bulk_operations_change_nothing_when_one_task_fails() {
+        let data = test_state().await;
+        let a = add_task(&data, "a", "todo").await;
+        let b = add_task(&data, "b", "todo").await;
+        let bulk = |request: serde_json::Value| bulk_tasks(data.clone(), web::Json(serde_json::from_value(request).unwrap()));
+
+        let (status, body) = body_json(
+            bulk(serde_json::json!({ "ids": [a.id, b.id, 9999], "operation": { "type": "delete" } })).await.unwrap(),
+        )
+        .await;
+        assert_eq!(status, 422);
+        assert_eq!(body["results"][2]["error"], "task not found");
+        let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tasks").fetch_one(&data.pool).await.unwrap();
+        assert_eq!(remaining, 2);
+
+        let (status, _) = body_json(
+            bulk(serde_json::json!({ "ids": [a.id, b.id], "operation": { "type": "add_tags", "tags": ["x"] } }))
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, 200);
+        let tags: Vec<String> = sqlx::query_scalar("SELECT tags FROM tasks ORDER BY id").fetch_all(&data.pool).await.unwrap();
+        assert_eq!(tags, vec!["[\"x\"]", "[\"x\"]"]);
+    }
+
+    #[actix_web::test]
+    async fn draft_commits_are_all_or_nothing() {
+        let data = test_state().await;
+        let tasks = serde_json::to_string(&vec![new_task("first", "todo"), new_task("second", "todo")]).unwrap();
+        let draft_id: i64 = sqlx::query_scalar("INSERT INTO task_drafts (meeting_notes, tasks) VALUES ('notes', ?) RETURNING id")
+            .bind(tasks)
+            .fetch_one(&data.pool)
+            .await
+            .unwrap();
+        let commit = || {
+            commit_task_draft(
+                actix_web::test::TestRequest::default().to_http_request(),
+                data.clone(),
+                web::Path::from(draft_id),
+                web::Query(DuplicateQuery { on_duplicate: DuplicateMode::default(), threshold: None }),
+                web::Bytes::new(),
+            )
+        };
+        let count = || async { sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM tasks").fetch_one(&data.pool).await.unwrap() };
+
+        set_wip_limit(&data, "todo", 1, "reject").await;
+        let (status, body) = body_json(commit().await.unwrap()).await;
+        assert_eq!((status, body["index"].clone()), (422, serde_json::json!(1)));
+        assert_eq!(count().await, 0);
+
+        sqlx::query("DELETE FROM wip_limits").execute(&data.pool).await.unwrap();
+        let (status, body) = body_json(commit().await.unwrap()).await;
+        assert_eq!(status, 201);
+        assert_eq!(body.as_array().unwrap().len(), 2);
+        assert_eq!(count().await, 2);
+        assert_eq!(body_json(commit().await.unwrap()).await.0, 404);
+    }
+
+    #[test]
+    fn parse_extraction_accepts_objects_and_bare_arrays() {
+        let items = parse_extraction("```json\n{\"tasks\": [{\"title\": \"a\"}]}\n```").unwrap();
+        assert_eq!(items, vec![serde_json::json!({ "title": "a" })]);
+        assert_eq!(parse_extraction("[{\"title\": \"b\"}]").unwrap().len(), 1);
+        assert!(parse_extraction("{\"items\": []}").is_err());
+        assert!(parse_extraction("not json").is_err());
+    }
+
+    #[test]
+    fn parse_task_item_fills_defaults_and_reports_bad_fields() {
+        let reference = date("2026-10-16");
+        let task = parse_task_item(
+            &serde_json::json!({ "title": " Ship it ", "deadline": "next friday" }),
+            reference,
+            &deadline_config(),
+        )
+        .unwrap();
+        assert_eq!(
+            (task.title.as_str(), task.project.as_str(), task.assignee.as_str(), task.status.as_str()),
+            ("Ship it", "General", "Unassigned", "todo")
+        );
+        assert_eq!(task.tags, vec!["ai-generated"]);
+        assert_eq!(task.deadline.as_deref(), Some("2026-10-23"));
+
+        let errors = parse_task_item(
+            &serde_json::json!({ "title": "", "tags": "x", "deadline": 5 }),
+            reference,
+            &deadline_config(),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            errors,
+            vec!["title must not be empty", "deadline must be null or a string", "tags must be an array of strings"]
+        );
+        assert_eq!(
+            parse_task_item(&serde_json::json!("task"), reference, &deadline_config()).err().unwrap(),
+            vec!["item must be an object"]
+        );
+        let undated = parse_task_item(&serde_json::json!({ "title": "t", "deadline": "someday" }), reference, &deadline_config());
+        assert_eq!(undated.ok().unwrap().deadline, None);
+    }
+
+    #[test]
+    fn chunk_notes_overlap_and_split_long_lines() {
+        let notes = ["a".repeat(8), "b".repeat(8), "c".repeat(8)].join("\n");
+        assert_eq!(
+            chunk_notes(&notes, 5, 3),
+            vec![format!("{}\n{}", "a".repeat(8), "b".repeat(8)), format!("{}\n{}", "b".repeat(8), "c".repeat(8))]
+        );
+        assert_eq!(chunk_notes(&notes, 100, 3), vec![notes.clone()]);
+        assert_eq!(chunk_notes(&"x".repeat(50), 5, 0), vec!["x".repeat(20), "x".repeat(20), "x".repeat(10)]);
+        assert_eq!(chunk_notes(&"é".repeat(15), 5, 0), vec!["é".repeat(10), "é".repeat(5)]);
+        assert!(chunk_notes("  \n", 5, 0).is_empty());
+    }
+
+    #[test]
+    fn circuit_breaker_opens_after_consecutive_outages() {
+        let breaker = CircuitBreaker {
+            threshold: 2,
+            cooldown: std::time::Duration::from_secs(60),
+            state: std::sync::Mutex::new((0, None)),
+        };
+        let outage: Result<(), AiError> = Err(AiError::Unavailable("503".to_string()));
+        breaker.record(&outage);
+        breaker.record(&Ok(()));
+        breaker.record(&outage);
+        breaker.record(&Err::<(), _>(AiError::InvalidResponse("bad json".to_string())));
+        assert!(!breaker.is_open());
+        breaker.record(&Err::<(), _>(AiError::Request("timeout".to_string())));
+        assert!(breaker.is_open());
+        breaker.record(&Ok(()));
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn backoff_doubles_and_honours_retry_after() {
+        let ms = |attempt, retry_after: Option<&str>| {
+            let header = retry_after.map(|v| reqwest::header::HeaderValue::from_str(v).unwrap());
+            AiHttp::backoff(attempt, header.as_ref()).as_millis()
+        };
+        assert_eq!((ms(0, None), ms(1, None), ms(2, None)), (500, 1000, 2000));
+        assert_eq!(ms(10, None), 30_000);
+        assert_eq!(ms(0, Some(" 7 ")), 7_000);
+        assert_eq!(ms(0, Some("600")), 60_000);
+        assert_eq!(ms(1, Some("Wed, 21 Oct 2026 07:28:00 GMT")), 1000);
+    }
+
+    #[test]
+    fn parse_task_filter_drops_empty_values_and_checks_statuses_and_dates() {
+        let filter = parse_task_filter(
+            "```json\n{\"assignee\": \"bob\", \"status\": \"todo, blocked\", \"tag\": \" \", \"deadline_before\": \"2026-10-31\"}\n```",
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&filter).unwrap(),
+            serde_json::json!({ "assignee": "bob", "status": "todo, blocked", "deadline_before": "2026-10-31" })
+        );
+        assert_eq!(parse_task_filter("{\"status\": \"todo,open\"}").unwrap_err(), "unknown status \"open\"");
+        assert_eq!(
+            parse_task_filter("{\"deadline_after\": \"next week\"}").unwrap_err(),
+            "\"next week\" is not a YYYY-MM-DD date"
+        );
+
+        let mut assignee = filter.assignee;
+        resolve_filter_names(&known(&["Bob Smith", "Alice"]), &mut assignee);
+        assert_eq!(assignee.as_deref(), Some("Bob Smith"));
+    }
+
+    #[test]
+    fn parse_breakdown_cleans_subtasks() {
+        let subtasks = parse_breakdown(
+            r#"{"subtasks": [
+                {"title": " Write schema ", "description": " d ", "tags": ["db", " "], "estimate_hours": 3},
+                {"title": "Review", "estimate_hours": -1},
+                {"title": "  ", "estimate_hours": 2},
+                {"description": "no title"}
+            ]}"#,
+        )
+        .unwrap();
+        let summary: Vec<(&str, &str, &[String], Option<f64>)> = subtasks
+            .iter()
+            .map(|s| (s.title.as_str(), s.description.as_str(), s.tags.as_slice(), s.estimate_hours))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("Write schema", "d", &["db".to_string()][..], Some(3.0)), ("Review", "", &[][..], None)]
+        );
+        let many = serde_json::to_string(&vec![serde_json::json!({ "title": "t" }); 25]).unwrap();
+        assert_eq!(parse_breakdown(&many).unwrap().len(), 20);
+        assert_eq!(parse_breakdown("[{\"title\": \"\"}]").err().unwrap(), "no subtasks with a title");
+        assert!(parse_breakdown("{\"tasks\": []}").is_err());
+    }
+
+    #[test]
+    fn detect_language_by_script_and_stopwords() {
+        assert_eq!(detect_language("来週までに資料を作成します"), Some("ja"));
+        assert_eq!(detect_language("下周完成报告"), Some("zh"));
+        assert_eq!(detect_language("다음 주까지 보고서를 작성합니다"), Some("ko"));
+        assert_eq!(detect_language("Подготовить отчёт к пятнице"), Some("ru"));
+        assert_eq!(detect_language("We will ship the release and update the docs for the team"), Some("en"));
+        assert_eq!(detect_language("Wir müssen das Release prüfen und die Doku ist nicht fertig"), Some("de"));
+        assert_eq!(detect_language("Nous devons finir les tests pour la démo avec une équipe"), Some("fr"));
+        assert_eq!(detect_language("Ship release"), None);
+        assert_eq!(detect_language("2026-10-16 -- 42"), None);
+    }
+
+    #[test]
+    fn project_keys_derive_from_names() {
+        assert_eq!(derive_project_key("Backend Service"), "BS");
+        assert_eq!(derive_project_key("infrastructure"), "INF");
+        assert_eq!(derive_project_key("Mobile app for iOS and Android"), "MAFI");
+        assert_eq!(derive_project_key("2026 launch"), "P2L");
+    }
+
+    #[actix_web::test]
+    async fn moved_tasks_get_a_new_key_and_keep_the_old_one() {
+        let data = test_state().await;
+        let (status, project) = body_json(
+            create_project(data.clone(), web::Json(CreateProjectRequest { name: "Backend Service".to_string(), key: None }))
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!((status, project["key"].as_str()), (201, Some("BS")));
+        let first = add_task(&data, "first", "todo").await;
+        let second = add_task(&data, "second", "todo").await;
+        let old_key = first.key.clone().unwrap();
+        assert_ne!(Some(old_key.as_str()), second.key.as_deref());
+
+        let (status, body) = body_json(
+            bulk_tasks(
+                data.clone(),
+                web::Json(
+                    serde_json::from_value(serde_json::json!({
+                        "ids": [first.id],
+                        "operation": { "type": "set_project", "project": "Backend Service" }
+                    }))
+                    .unwrap(),
+                ),
+            )
+            .await
+            .unwrap(),
+        )
+        .await;
+        assert_eq!((status, body["results"][0]["task"]["key"].as_str()), (200, Some("BS-1")));
+        let response = get_task(data.clone(), web::Path::from(old_key.to_lowercase()), web::Query(GetTaskQuery { expand: None }))
+            .await
+            .unwrap();
+        assert_eq!(response.headers().get("Location").unwrap(), "/tasks/BS-1");
+    }
+
+    #[actix_web::test]
+    async fn jobs_run_fail_and_cancel() {
+        let data = test_state().await;
+        let enqueue = |payload: &str| {
+            sqlx::query_scalar::<_, i64>("INSERT INTO jobs (kind, payload) VALUES ('extract_tasks', ?) RETURNING id")
+                .bind(payload.to_string())
+                .fetch_one(&data.pool)
+        };
+        let job = |id: i64| {
+            let pool = data.pool.clone();
+            async move { fetch_job(&pool, id).await.unwrap().unwrap() }
+        };
+
+        let extract = enqueue(r#"{"type": "extract_tasks", "notes": {"meeting_notes": "- Write docs"}, "query": {}}"#)
+            .await
+            .unwrap();
+        let broken = enqueue(r#"{"type": "unknown"}"#).await.unwrap();
+        for expected in [extract, broken] {
+            let (id, payload, attempts, max_attempts) = claim_job(&data.pool).await.unwrap().unwrap();
+            assert_eq!((id, attempts), (expected, 1));
+            run_job(&data, id, &payload, attempts, max_attempts).await.unwrap();
+        }
+        let done = job(extract).await;
+        assert_eq!(done.status, "succeeded");
+        assert_eq!(done.result.unwrap()["tasks"][0]["title"], "Write docs");
+        let failed = job(broken).await;
+        assert_eq!(failed.status, "failed");
+        assert!(failed.error.unwrap().starts_with("invalid job payload"));
+
+        let queued = enqueue(r#"{"type": "extract_tasks", "notes": {"meeting_notes": "- Later"}, "query": {}}"#)
+            .await
+            .unwrap();
+        let (status, body) = body_json(cancel_job(data.clone(), web::Path::from(queued)).await.unwrap()).await;
+        assert_eq!((status, body["status"].as_str()), (200, Some("cancelled")));
+        assert!(claim_job(&data.pool).await.unwrap().is_none());
+        assert_eq!(body_json(cancel_job(data.clone(), web::Path::from(queued)).await.unwrap()).await.0, 409);
+    }
+
+    #[actix_web::test]
+    async fn usage_is_priced_and_capped_by_the_monthly_budget() {
+        let mut state = std::sync::Arc::try_unwrap(test_state().await.into_inner()).ok().unwrap();
+        state.budget = AiBudget {
+            prices: HashMap::from([("fake".to_string(), (1.0, 4.0))]),
+            monthly_tokens: Some(1_000_000),
+            monthly_cost: Some(2.0),
+        };
+        let data = web::Data::new(state);
+        let metered = MeteredProvider {
+            inner: data.ai.clone(),
+            data: data.clone(),
+            operation: "extract",
+            user: Some("ana".to_string()),
+            job_id: None,
+        };
+        let usage = TokenUsage { prompt_tokens: 200_000, completion_tokens: 200_000 };
+        metered.record_call(std::time::Instant::now(), "fake", "fake", usage, None).await;
+        let cost: Option<f64> = sqlx::query_scalar("SELECT cost_usd FROM ai_usage WHERE user='ana'").fetch_one(&data.pool).await.unwrap();
+        assert_eq!(cost, Some(1.0));
+        assert_eq!(budget_exceeded(&data).await.unwrap(), None);
+
+        metered.record_call(std::time::Instant::now(), "fake", "fake", usage, None).await;
+        assert_eq!(
+            budget_exceeded(&data).await.unwrap().as_deref(),
+            Some("monthly AI cost budget of $2.00 exceeded ($2.00 used)")
+        );
+        metered.record_call(std::time::Instant::now(), "fake", "fake", TokenUsage { prompt_tokens: 200_000, completion_tokens: 0 }, None).await;
+        assert_eq!(
+            budget_exceeded(&data).await.unwrap().as_deref(),
+            Some("monthly AI token budget of 1000000 exceeded (1000000 used)")
+        );
+        assert_eq!(ai_over_budget(&data).await.unwrap().unwrap().status().as_u16(), 429);
+    }
+
+    #[test]
+    fn summary_facts_list_changes_and_deadline_risks() {
+        let mut shipped = new_task("Ship", "done");
+        shipped.id = 1;
+        shipped.key = Some("BE-1".to_string());
+        shipped.deadline = Some("2026-10-10".to_string());
+        let mut late = new_task("Fix login", "in_progress");
+        late.id = 2;
+        late.deadline = Some("2026-10-14".to_string());
+        late.description = format!("line one\n{}", "x".repeat(400));
+        let mut later = new_task("Plan", "todo");
+        later.id = 3;
+        later.deadline = Some("2026-12-01".to_string());
+        let changes = vec![StatusChangeRow {
+            task_id: 1,
+            from_status: "in_progress".to_string(),
+            to_status: "done".to_string(),
+            comment: Some("merged".to_string()),
+            changed_at: "2026-10-12 09:30:00".to_string(),
+        }];
+
+        let facts = summary_facts(&[shipped, late, later], &changes, date("2026-10-05"), date("2026-10-16"), Some("General"), date("2026-10-16"));
+        assert!(facts.starts_with("Reporting period: 2026-10-05 to 2026-10-16\nProject: General\n"));
+        assert!(facts.contains(&format!("  Description: line one {}…\n", "x".repeat(291))));
+        assert!(facts.contains("- 2026-10-12 [BE-1] Ship: in_progress -> done (\"merged\")\n"));
+        assert!(facts.ends_with("not done):\n- Fix login: 2026-10-14 (overdue by 2 days)\n"));
+    }
+
+    #[test]
+    fn strip_markdown_fence_keeps_the_report() {
+        assert_eq!(strip_markdown_fence("```markdown\n# Report\n- done\n```\n"), "# Report\n- done");
+        assert_eq!(strip_markdown_fence("  # Report  "), "# Report");
+    }
+
+    #[actix_web::test]
+    async fn generated_tasks_link_to_their_meeting() {
+        let data = test_state().await;
+        let request: GenerateTasksRequest = serde_json::from_value(serde_json::json!({
+            "meeting_notes": "Standup\n- Write docs\n- Fix login",
+            "date": "2026-10-16",
+            "attendees": ["Ana"],
+        }))
+        .unwrap();
+        let (status, _) = body_json(
+            generate_tasks_from_ai(
+                actix_web::test::TestRequest::default().to_http_request(),
+                data.clone(),
+                web::Query(serde_json::from_value(serde_json::json!({})).unwrap()),
+                web::Json(request),
+            )
+            .await
+            .unwrap(),
+        )
+        .await;
+        assert_eq!(status, 202);
+        let (id, payload, attempts, max_attempts) = claim_job(&data.pool).await.unwrap().unwrap();
+        run_job(&data, id, &payload, attempts, max_attempts).await.unwrap();
+
+        let meeting_id: i64 = sqlx::query_scalar("SELECT id FROM meetings").fetch_one(&data.pool).await.unwrap();
+        let (status, body) = body_json(get_meeting(data.clone(), web::Path::from(meeting_id)).await.unwrap()).await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            (body["title"].as_str(), body["date"].as_str(), body["attendees"].clone()),
+            (Some("Standup"), Some("2026-10-16"), serde_json::json!(["Ana"]))
+        );
+        let titles: Vec<&str> = body["tasks"].as_array().unwrap().iter().filter_map(|t| t["title"].as_str()).collect();
+        assert_eq!(titles, vec!["Write docs", "Fix login"]);
+        assert!(body["tasks"].as_array().unwrap().iter().all(|t| t["meeting_id"] == meeting_id));
+    }
+
+    #[test]
+    fn providers_are_built_by_name_with_an_optional_model() {
+        let http = AiHttp::from_env();
+        let provider = build_ai_provider("OpenAI", Some("llama3.1"), &http).unwrap();
+        assert_eq!((provider.name(), provider.model()), ("OpenAI-compatible", "llama3.1"));
+        let provider = build_ai_provider("gemini", Some("gemini-2.5-pro"), &http).unwrap();
+        assert_eq!((provider.name(), provider.model()), ("Gemini", "gemini-2.5-pro"));
+        assert_eq!(build_ai_provider("xai", None, &http).unwrap().name(), "xAI");
+        assert!(build_ai_provider("claude", None, &http).is_none());
+    }
+}
@@ -80,6 +80,11 @@ interface Task {
   in_sprint?: boolean;
   notes?: string;
   archived?: boolean;
+  key?: string;
+  meeting_id?: number | null;
+  prompt_version?: number | null;
+  parent_id?: number | null;
+  estimate_hours?: number | null;
 }
 
 /** Calculate remaining working days until deadline (excludes weekends). */
@@ -124,6 +129,7 @@ const App: React.FC = () => {
   const [filterStatus, setFilterStatus] = useState<string[]>([]);
   const [meetingNotes, setMeetingNotes] = useState<string>('');
   const [generating, setGenerating] = useState(false);
+  const [generationProgress, setGenerationProgress] = useState<string | null>(null);
   const [error, setError] = useState<string | null>(null);
   const [loading, setLoading] = useState<boolean>(false);
   const [editingTaskId, setEditingTaskId] = useState<number | null>(null);
@@ -274,10 +280,25 @@ const App: React.FC = () => {
         headers: { 'Content-Type': 'application/json' },
         body: JSON.stringify({ meeting_notes: notes }),
       });
-      const data = await response.json();
+      let data = await response.json();
       if (!response.ok) {
         throw new Error(data?.error || `Failed to generate tasks: ${response.status}`);
       }
+      if (response.status === 202) {
+        // Generation runs as a background job; poll until it finishes
+        while (data.status === 'queued' || data.status === 'running') {
+          setGenerationProgress(`${data.completed}/${data.total}`);
+          await new Promise((resolve) => setTimeout(resolve, 1000));
+          const jobResponse = await fetch(`${API_BASE}/jobs/${data.id}`);
+          data = await jobResponse.json();
+        }
+        if (data.status === 'failed') {
+          throw new Error(data.error || 'Failed to generate tasks');
+        }
+        if (data.status === 'cancelled') {
+          throw new Error('Task generation was cancelled');
+        }
+      }
       await fetchTasks();
       setMeetingNotes('');
     } catch (err) {
@@ -286,6 +307,7 @@ const App: React.FC = () => {
       console.error('Error generating task:', err);
     } finally {
       setGenerating(false);
+      setGenerationProgress(null);
     }
   };
 
@@ -499,7 +521,7 @@ const App: React.FC = () => {
       title="Double-click to edit"
     >
       <div className="task-card-header">
-        <h3>{task.title}</h3>
+        <h3>{task.key && <span className="task-key">{task.key}</span>}{task.title}</h3>
         <select
           className="status-select"
           value={task.status || 'todo'}
@@ -795,7 +817,7 @@ const App: React.FC = () => {
           />
         </div>
         <button onClick={generateTasksFromNotes} disabled={generating}>
-          {generating ? 'Generating...' : 'Generate'}
+          {generating ? `Generating${generationProgress ? ` (${generationProgress})` : ''}...` : 'Generate'}
         </button>
         </div>
         )}
@@ -891,7 +913,7 @@ const App: React.FC = () => {
                     title="Double-click to edit"
                   >
                     <div className="task-card-header">
-                      <h3>{task.title}</h3>
+                      <h3>{task.key && <span className="task-key">{task.key}</span>}{task.title}</h3>
                       <button
                         type="button"
                         className="restore-btn"